@@ -0,0 +1,384 @@
+use base::*;
+use simulation::{Channel, ChannelKind, SimulationResult};
+use simulation_2d::NonHolonomicDynamics;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt;
+use tf_record::TfFeature;
+
+/// A transform applied to a simulated episode as it is written, to augment a dataset.
+/// Positions are reflected, then rotated about the origin, then translated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transform {
+    /// Reflect in the x axis (y -> -y), or in the origin for 1 dimension
+    pub reflect: bool,
+    /// Rotation about the origin, about the z axis for 3 dimensions
+    pub rotation: Radians,
+    /// Offset added along each axis
+    pub translation: Vec<f64>,
+    pub time_reversed: bool,
+    /// `permutation[i]` is the index of robot `i` in the transformed episode
+    pub permutation: Vec<usize>,
+}
+
+/// Wraps an angle into [-pi, pi)
+fn wrap_angle(angle: Radians) -> Radians {
+    let wrapped = (angle + PI) % (2. * PI);
+    if wrapped < 0. {
+        wrapped + PI
+    } else {
+        wrapped - PI
+    }
+}
+
+impl Transform {
+    pub fn identity(num_robots: usize, dimensions: usize) -> Transform {
+        Transform {
+            reflect: false,
+            rotation: 0.,
+            translation: vec![0.; dimensions],
+            time_reversed: false,
+            permutation: (0..num_robots).collect(),
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        !self.reflect
+            && self.rotation == 0.
+            && self.translation.iter().all(|&t| t == 0.)
+            && !self.time_reversed
+            && self.permutation.iter().enumerate().all(|(i, &p)| i == p)
+    }
+
+    pub fn validate(&self, num_robots: usize, dimensions: usize) -> Result<(), &'static str> {
+        if self.rotation != 0. && dimensions < 2 {
+            return Err("rotation needs at least 2 dimensions");
+        }
+        if self.translation.len() != dimensions {
+            return Err("translation must have one offset per dimension");
+        }
+        let mut seen = vec![false; num_robots];
+        for &p in self.permutation.iter() {
+            if p >= num_robots || seen[p] {
+                return Err("robot order must be a permutation of the robots");
+            }
+            seen[p] = true;
+        }
+        if self.permutation.len() != num_robots {
+            return Err("robot order must be a permutation of the robots");
+        }
+        Ok(())
+    }
+
+    pub fn position<S: Vector>(&self, position: S) -> S {
+        let mut repr = self.vector(position, false).repr();
+        for (value, offset) in repr.iter_mut().zip(self.translation.iter()) {
+            *value += offset;
+        }
+        S::from_repr(&repr)
+    }
+
+    /// Transforms a displacement, which is reflected and rotated but not translated.
+    /// Rates of change also turn around when time is reversed.
+    pub fn vector<S: Vector>(&self, vector: S, is_rate: bool) -> S {
+        let mut repr = vector.repr();
+        if self.reflect {
+            let axis = if repr.len() == 1 { 0 } else { 1 };
+            repr[axis] = -repr[axis];
+        }
+        if repr.len() >= 2 {
+            let (sin, cos) = self.rotation.sin_cos();
+            let (x, y) = (repr[0], repr[1]);
+            repr[0] = x * cos - y * sin;
+            repr[1] = x * sin + y * cos;
+        }
+        if is_rate && self.time_reversed {
+            for value in repr.iter_mut() {
+                *value = -*value;
+            }
+        }
+        S::from_repr(&repr)
+    }
+
+    /// Transforms a heading in the direction of motion, which turns around when time is reversed
+    pub fn heading(&self, heading: Radians) -> Radians {
+        let heading = if self.reflect { -heading } else { heading };
+        let heading = if self.time_reversed {
+            heading + PI
+        } else {
+            heading
+        };
+        wrap_angle(heading + self.rotation)
+    }
+
+    /// Transforms the state of a vehicle. Its heading is the way it faces,
+    /// so a reversed vehicle keeps its heading but drives backwards.
+    pub fn dynamics(&self, dynamics: &NonHolonomicDynamics) -> NonHolonomicDynamics {
+        let heading = if self.reflect {
+            -dynamics.heading
+        } else {
+            dynamics.heading
+        };
+        let direction = if self.time_reversed { -1. } else { 1. };
+        let handedness = if self.reflect { -1. } else { 1. };
+        NonHolonomicDynamics {
+            position: self.position(dynamics.position),
+            heading: wrap_angle(heading + self.rotation),
+            speed: direction * dynamics.speed,
+            angular_velocity: direction * handedness * dynamics.angular_velocity,
+        }
+    }
+
+    fn channel_vector<S: Vector>(&self, vector: S, kind: ChannelKind) -> S {
+        match kind {
+            ChannelKind::Position => self.position(vector),
+            ChannelKind::Displacement => self.vector(vector, false),
+            ChannelKind::Rate => self.vector(vector, true),
+        }
+    }
+
+    /// The index of a robot in the transformed episode
+    pub fn robot_index(&self, robot: usize) -> usize {
+        self.permutation[robot]
+    }
+
+    /// Relabels which robot each robot follows, `parents[i]` being the robot robot `i` follows
+    pub fn parents(&self, parents: &[usize]) -> Vec<usize> {
+        let mut transformed = vec![0; parents.len()];
+        for (robot, &parent) in parents.iter().enumerate() {
+            transformed[self.permutation[robot]] = self.permutation[parent];
+        }
+        transformed
+    }
+
+    /// Reorders per robot data, first dimension robot number, second dimension step
+    pub fn reorder<T>(&self, data: Vec<Vec<T>>) -> Vec<Vec<T>> {
+        let mut slots: Vec<Option<Vec<T>>> = data.iter().map(|_| None).collect();
+        for (robot, mut robot_data) in data.into_iter().enumerate() {
+            if self.time_reversed {
+                robot_data.reverse();
+            }
+            slots[self.permutation[robot]] = Some(robot_data);
+        }
+        slots.into_iter().map(|d| d.unwrap()).collect()
+    }
+
+    pub fn result<S: Vector, R: SimulationResult<S>>(&self, result: R) -> TransformedResult<S> {
+        let time_step = result.time_step();
+        let path_error = result.path_error();
+        let headings = result.headings().cloned().map(|headings| {
+            let headings = headings
+                .into_iter()
+                .map(|h| h.into_iter().map(|h| self.heading(h)).collect())
+                .collect();
+            self.reorder(headings)
+        });
+        let channels = result
+            .channels()
+            .iter()
+            .map(|channel| {
+                let data = channel
+                    .data
+                    .iter()
+                    .map(|d| d.iter().map(|&v| self.channel_vector(v, channel.kind)).collect())
+                    .collect();
+                Channel {
+                    data: self.reorder(data),
+                    ..channel.clone()
+                }
+            })
+            .collect();
+        let data = result
+            .into_data()
+            .into_iter()
+            .map(|d| d.into_iter().map(|p| self.position(p)).collect())
+            .collect();
+        TransformedResult {
+            time_step,
+            data: self.reorder(data),
+            path_error,
+            headings,
+            channels,
+        }
+    }
+
+    /// Features recording the transform, stored with each record
+    pub fn features(&self) -> HashMap<String, TfFeature> {
+        let mut features = HashMap::with_capacity(5);
+        features.insert(
+            "transform_reflect".to_string(),
+            TfFeature::Ints(vec![self.reflect as i64]),
+        );
+        features.insert(
+            "transform_rotation".to_string(),
+            TfFeature::Floats(vec![self.rotation]),
+        );
+        features.insert(
+            "transform_translation".to_string(),
+            TfFeature::Floats(self.translation.clone()),
+        );
+        features.insert(
+            "transform_time_reversed".to_string(),
+            TfFeature::Ints(vec![self.time_reversed as i64]),
+        );
+        features.insert(
+            "transform_permutation".to_string(),
+            TfFeature::Ints(self.permutation.iter().map(|&p| p as i64).collect()),
+        );
+        features
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_identity() {
+            return write!(f, "identity");
+        }
+        let mut parts: Vec<String> = Vec::new();
+        if self.reflect {
+            parts.push("reflect".to_string());
+        }
+        if self.rotation != 0. {
+            parts.push(format!("rotate({})", self.rotation));
+        }
+        if self.translation.iter().any(|&t| t != 0.) {
+            let offsets: Vec<String> = self.translation.iter().map(|t| t.to_string()).collect();
+            parts.push(format!("translate({})", offsets.join(",")));
+        }
+        if self.time_reversed {
+            parts.push("reverse".to_string());
+        }
+        if self.permutation.iter().enumerate().any(|(i, &p)| i != p) {
+            let order: Vec<String> = self.permutation.iter().map(|p| p.to_string()).collect();
+            parts.push(format!("permute({})", order.join(",")));
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// A simulation result after a `Transform`
+#[derive(Debug, Clone)]
+pub struct TransformedResult<S: Vector> {
+    time_step: Seconds,
+    data: Vec<Vec<S>>,
+    path_error: Option<f64>,
+    headings: Option<Vec<Vec<Radians>>>,
+    channels: Vec<Channel<S>>,
+}
+
+impl<S: Vector> SimulationResult<S> for TransformedResult<S> {
+    fn time_step(&self) -> Seconds {
+        self.time_step
+    }
+
+    fn num_robots(&self) -> usize {
+        self.data.len()
+    }
+
+    fn num_steps(&self) -> usize {
+        self.data.first().map_or(0, |d| d.len())
+    }
+
+    fn path_error(&self) -> Option<f64> {
+        self.path_error
+    }
+
+    fn headings(&self) -> Option<&Vec<Vec<Radians>>> {
+        self.headings.as_ref()
+    }
+
+    fn channels(&self) -> &[Channel<S>] {
+        &self.channels
+    }
+
+    fn into_data(self) -> Vec<Vec<S>> {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn transforms() {
+        let mut transform = Transform::identity(3, 2);
+        assert!(transform.is_identity());
+        assert_eq!(transform.to_string(), "identity");
+        assert_eq!(
+            transform.position(Metres2D::new(1., 2.)),
+            Metres2D::new(1., 2.)
+        );
+
+        // reflect, then rotate a quarter turn, then translate
+        transform.reflect = true;
+        transform.rotation = PI / 2.;
+        transform.translation = vec![10., 0.];
+        let p = transform.position(Metres2D::new(1., 2.));
+        assert!(close(p.x, 12.) && close(p.y, 1.));
+        assert!(close(transform.heading(PI / 4.), PI / 4.));
+        let mut transform_1d = Transform::identity(1, 1);
+        transform_1d.reflect = true;
+        assert!(close(transform_1d.position(1f64), -1.));
+
+        // the z axis is untouched by reflection and rotation
+        let mut transform_3d = Transform::identity(1, 3);
+        transform_3d.reflect = true;
+        transform_3d.rotation = PI;
+        let p = transform_3d.position(Metres3D::new(1., 2., 3.));
+        assert!(close(p.x, -1.) && close(p.y, 2.) && close(p.z, 3.));
+
+        // displacements are not translated, and rates turn around when time is reversed
+        let v = transform.vector(Metres2D::new(1., 2.), true);
+        assert!(close(v.x, 2.) && close(v.y, 1.));
+
+        // time reversal turns the direction of motion around
+        transform.time_reversed = true;
+        let v = transform.vector(Metres2D::new(1., 2.), true);
+        assert!(close(v.x, -2.) && close(v.y, -1.));
+        assert!(close(transform.heading(PI / 4.), -3. * PI / 4.));
+
+        transform.permutation = vec![2, 0, 1];
+        assert!(transform.validate(3, 2).is_ok());
+        assert!(Transform::identity(3, 1).validate(3, 2).is_err());
+        let data = vec![vec![0, 1], vec![10, 11], vec![20, 21]];
+        assert_eq!(
+            transform.reorder(data),
+            vec![vec![11, 10], vec![21, 20], vec![1, 0]]
+        );
+        assert_eq!(transform.robot_index(0), 2);
+        assert_eq!(transform.parents(&[1, 1, 1]), vec![0, 0, 0]);
+        assert_eq!(transform.parents(&[0, 0, 1]), vec![2, 0, 2]);
+
+        let mut bad = Transform::identity(3, 2);
+        bad.permutation = vec![0, 0, 1];
+        assert!(bad.validate(3, 2).is_err());
+        bad.permutation = vec![0, 1];
+        assert!(bad.validate(3, 2).is_err());
+        bad = Transform::identity(2, 1);
+        bad.rotation = 1.;
+        assert!(bad.validate(2, 1).is_err());
+    }
+
+    #[test]
+    fn transformed_dynamics() {
+        let mut transform = Transform::identity(1, 2);
+        transform.reflect = true;
+        transform.rotation = PI;
+        let dynamics = NonHolonomicDynamics {
+            position: Metres2D::new(1., 1.),
+            heading: PI / 4.,
+            speed: 2.,
+            angular_velocity: 0.5,
+        };
+        let transformed = transform.dynamics(&dynamics);
+        assert!(close(transformed.position.x, -1.) && close(transformed.position.y, 1.));
+        assert!(close(transformed.heading, 3. * PI / 4.));
+        assert!(close(transformed.speed, 2.));
+        assert!(close(transformed.angular_velocity, -0.5));
+    }
+}
@@ -27,6 +27,17 @@ pub trait Vector:
     fn repr_length() -> usize;
 
     fn repr(&self) -> Vec<f64>;
+
+    /// The inverse of `repr`
+    fn from_repr(repr: &[f64]) -> Self;
+
+    fn dot(&self, other: &Self) -> f64 {
+        self.repr()
+            .into_iter()
+            .zip(other.repr())
+            .map(|(a, b)| a * b)
+            .sum()
+    }
 }
 
 pub type Seconds = f64;
@@ -47,6 +58,10 @@ impl Vector for f64 {
     fn repr(&self) -> Vec<f64> {
         vec![*self]
     }
+
+    fn from_repr(repr: &[f64]) -> f64 {
+        repr[0]
+    }
 }
 
 #[derive(PartialEq, PartialOrd, Copy, Clone, Debug, Default, Deserialize, Serialize)]
@@ -147,6 +162,142 @@ impl Vector for Metres2D {
     fn repr(&self) -> Vec<f64> {
         vec![self.x, self.y]
     }
+
+    fn from_repr(repr: &[f64]) -> Metres2D {
+        Metres2D::new(repr[0], repr[1])
+    }
+}
+
+#[derive(PartialEq, PartialOrd, Copy, Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Metres3D {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+pub type Metres3DPerSecond = Metres3D;
+
+impl Add for Metres3D {
+    type Output = Metres3D;
+
+    fn add(self, rhs: Metres3D) -> Metres3D {
+        Metres3D {
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
+        }
+    }
+}
+
+impl Sub for Metres3D {
+    type Output = Metres3D;
+
+    fn sub(self, rhs: Metres3D) -> Metres3D {
+        Metres3D {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+        }
+    }
+}
+
+impl Mul<f64> for Metres3D {
+    type Output = Metres3D;
+
+    fn mul(self, rhs: f64) -> Metres3D {
+        Metres3D {
+            x: self.x * rhs,
+            y: self.y * rhs,
+            z: self.z * rhs,
+        }
+    }
+}
+
+impl Div<f64> for Metres3D {
+    type Output = Metres3D;
+
+    fn div(self, rhs: f64) -> Metres3D {
+        Metres3D {
+            x: self.x / rhs,
+            y: self.y / rhs,
+            z: self.z / rhs,
+        }
+    }
+}
+
+impl Zero for Metres3D {
+    fn zero() -> Self {
+        Metres3D {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.x == 0. && self.y == 0. && self.z == 0.
+    }
+}
+
+impl AddAssign for Metres3D {
+    fn add_assign(&mut self, rhs: Metres3D) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Metres3D {
+    fn sub_assign(&mut self, rhs: Metres3D) {
+        *self = *self - rhs;
+    }
+}
+
+impl MulAssign<f64> for Metres3D {
+    fn mul_assign(&mut self, rhs: f64) {
+        *self = *self * rhs;
+    }
+}
+
+impl DivAssign<f64> for Metres3D {
+    fn div_assign(&mut self, rhs: f64) {
+        *self = *self / rhs;
+    }
+}
+
+impl Vector for Metres3D {
+    fn length(&self) -> f64 {
+        (self.x.powi(2) + self.y.powi(2) + self.z.powi(2)).sqrt()
+    }
+
+    fn repr_length() -> usize {
+        3
+    }
+
+    fn repr(&self) -> Vec<f64> {
+        vec![self.x, self.y, self.z]
+    }
+
+    fn from_repr(repr: &[f64]) -> Metres3D {
+        Metres3D::new(repr[0], repr[1], repr[2])
+    }
+}
+
+impl Metres3D {
+    pub fn new(x: f64, y: f64, z: f64) -> Metres3D {
+        Metres3D { x, y, z }
+    }
+
+    /// Combines a horizontal position with an altitude
+    pub fn from_horizontal(horizontal: Metres2D, z: f64) -> Metres3D {
+        Metres3D {
+            x: horizontal.x,
+            y: horizontal.y,
+            z,
+        }
+    }
+
+    pub fn horizontal(&self) -> Metres2D {
+        Metres2D::new(self.x, self.y)
+    }
 }
 
 pub struct PolarMetres2D {
@@ -7,7 +7,7 @@ pub const EDUBBADRHO: u32 = 3;
 pub const EDUBNOPATH: u32 = 4;
 
 #[repr(C)]
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum DubinsPathType {
     LSL,
     LSR,
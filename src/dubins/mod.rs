@@ -3,12 +3,10 @@ use failure::Fail;
 use num::Zero;
 use std::f64;
 use std::f64::consts::PI;
-use std::fmt;
 use std::fmt::Display;
 use std::fmt::Error as FmtError;
 use std::fmt::Formatter;
 use std::iter;
-use std::iter::Peekable;
 use std::ops::Add;
 use std::slice;
 
@@ -17,67 +15,12 @@ pub mod bindings;
 pub use self::bindings::DubinsPathType;
 use rand::distributions::{Distribution, Uniform};
 use rand::Rng;
+use serde::de::Error as DeError;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cell::Cell;
 
-#[derive(Clone)]
-struct PeekableMap<I, F> {
-    iter: I,
-    fun: F,
-}
-
-impl<I, F> PeekableMap<I, F> {
-    pub fn new(iter: I, fun: F) -> Self {
-        PeekableMap { iter, fun }
-    }
-}
-
-impl<I: fmt::Debug, F> fmt::Debug for PeekableMap<I, F> {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        f.debug_struct("PeekableMap")
-            .field("iter", &self.iter)
-            .finish()
-    }
-}
-
-impl<B, I, F> Iterator for PeekableMap<Peekable<I>, F>
-where
-    I: Iterator,
-    F: FnMut(<Peekable<I> as Iterator>::Item, Option<&<Peekable<I> as Iterator>::Item>) -> B,
-{
-    type Item = B;
-
-    #[inline]
-    fn next(&mut self) -> Option<B> {
-        self.iter
-            .next()
-            .map(|item| (self.fun)(item, self.iter.peek()))
-    }
-
-    #[inline]
-    fn size_hint(&self) -> (usize, Option<usize>) {
-        self.iter.size_hint()
-    }
-}
-
-trait WithPeekableMap: Iterator {
-    fn map_with_peek<F, B>(self, f: F) -> PeekableMap<Self, F>
-    where
-        F: FnMut(<Self as Iterator>::Item, Option<&<Self as Iterator>::Item>) -> B,
-        Self: Sized;
-}
-
-impl<I> WithPeekableMap for Peekable<I>
-where
-    I: Iterator,
-{
-    fn map_with_peek<F, B>(self, f: F) -> PeekableMap<Self, F>
-    where
-        F: FnMut(<Self as Iterator>::Item, Option<&<Self as Iterator>::Item>) -> B,
-        Self: Sized,
-    {
-        PeekableMap::new(self, f)
-    }
-}
+/// How many random waypoints to try before giving up on finding a leg of an allowed path type
+const MAX_GENERATION_ATTEMPTS: usize = 1000;
 
 #[derive(Debug, Fail)]
 pub enum DubinsError {
@@ -85,6 +28,8 @@ pub enum DubinsError {
     PathParametrisationError,
     BadRho,
     NoPath,
+    NotEnoughWaypoints,
+    BadSpeed,
     Unknown(u32),
 }
 
@@ -95,6 +40,8 @@ impl Display for DubinsError {
             DubinsError::PathParametrisationError => write!(f, "Path parametrisation error"),
             DubinsError::BadRho => write!(f, "Rho value was invalid"),
             DubinsError::NoPath => write!(f, "No path connecting the configurations"),
+            DubinsError::NotEnoughWaypoints => write!(f, "At least two waypoints are required"),
+            DubinsError::BadSpeed => write!(f, "Speed must be positive"),
             DubinsError::Unknown(code) => write!(f, "Unknown error code: {}", code),
         }
     }
@@ -113,6 +60,39 @@ impl DubinsError {
     }
 }
 
+/// The direction of travel along one of the three segments of a Dubins path
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SegmentType {
+    Left,
+    Straight,
+    Right,
+}
+
+impl SegmentType {
+    /// Signed curvature of the segment, with anticlockwise turns being positive
+    pub fn curvature(&self, turning_radius: Metres) -> f64 {
+        match *self {
+            SegmentType::Left => 1. / turning_radius,
+            SegmentType::Straight => 0.,
+            SegmentType::Right => -1. / turning_radius,
+        }
+    }
+}
+
+impl DubinsPathType {
+    pub fn segment_types(&self) -> [SegmentType; 3] {
+        use self::SegmentType::*;
+        match *self {
+            DubinsPathType::LSL => [Left, Straight, Left],
+            DubinsPathType::LSR => [Left, Straight, Right],
+            DubinsPathType::RSL => [Right, Straight, Left],
+            DubinsPathType::RSR => [Right, Straight, Right],
+            DubinsPathType::RLR => [Right, Left, Right],
+            DubinsPathType::LRL => [Left, Right, Left],
+        }
+    }
+}
+
 type Configuration = [f64; 3];
 
 trait ConvertibleToConfiguration {
@@ -135,12 +115,96 @@ impl ConvertibleToOrientedPosition2D for Configuration {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Serialised as its start configuration, turning radius, word and the lengths of its three segments in metres,
+/// for example:
+///
+/// ```yaml
+/// start: {x: 0.0, y: 0.0, r: 0.0}
+/// turning_radius: 1.0
+/// word: RSR
+/// segment_lengths: [1.5707963267948966, 2.0, 0.0]
+/// ```
+///
+/// Dumps of the raw C structure (`inner` and `end`) are also accepted when deserialising.
+/// If an `end` configuration is given, the path is rejected unless it ends there.
+#[derive(Debug, Clone)]
 pub struct DubinsPath {
     inner: Cell<bindings::DubinsPath>,
     end: OrientedPosition2D,
 }
 
+#[derive(Serialize, Deserialize)]
+struct DubinsPathSpec {
+    start: OrientedPosition2D,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end: Option<OrientedPosition2D>,
+    turning_radius: Metres,
+    word: DubinsPathType,
+    segment_lengths: [Metres; 3],
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DubinsPathRepr {
+    Spec(DubinsPathSpec),
+    Legacy {
+        inner: bindings::DubinsPath,
+        end: OrientedPosition2D,
+    },
+}
+
+impl Serialize for DubinsPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DubinsPathSpec {
+            start: self.start(),
+            end: None,
+            turning_radius: self.turning_radius(),
+            word: self.path_type(),
+            segment_lengths: [
+                self.segment_length(0),
+                self.segment_length(1),
+                self.segment_length(2),
+            ],
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DubinsPath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let spec = match DubinsPathRepr::deserialize(deserializer)? {
+            DubinsPathRepr::Spec(spec) => spec,
+            DubinsPathRepr::Legacy { inner, end } => DubinsPathSpec {
+                start: inner.qi.to_oriented_position(),
+                end: Some(end),
+                turning_radius: inner.rho,
+                word: inner.type_,
+                segment_lengths: [
+                    inner.param[0] * inner.rho,
+                    inner.param[1] * inner.rho,
+                    inner.param[2] * inner.rho,
+                ],
+            },
+        };
+        let mut path = DubinsPath::from_segments(
+            spec.start,
+            spec.turning_radius,
+            spec.word,
+            spec.segment_lengths,
+        ).map_err(D::Error::custom)?;
+        if let Some(end) = spec.end {
+            let heading_diff = ((end.rotation - path.end.rotation) / 2.).sin().abs();
+            if (end.position - path.end.position).length() > 1e-6 || heading_diff > 1e-6 {
+                return Err(D::Error::custom(format!(
+                    "the path ends at {:?}, not at the given end {:?}",
+                    path.end, end
+                )));
+            }
+            path.end = end;
+        }
+        Ok(path)
+    }
+}
+
 unsafe extern "C" fn dubins_sampling_callback(
     q: *mut f64,
     t: f64,
@@ -203,6 +267,40 @@ impl DubinsPath {
         })
     }
 
+    /// Builds a path directly from its segments, given as lengths in metres
+    pub fn from_segments(
+        start: OrientedPosition2D,
+        turning_radius: f64,
+        shape: DubinsPathType,
+        segment_lengths: [f64; 3],
+    ) -> Result<Self, DubinsError> {
+        if !turning_radius.is_finite() || turning_radius <= 0. {
+            return Err(DubinsError::BadRho);
+        }
+        if segment_lengths
+            .iter()
+            .any(|length| !length.is_finite() || *length < 0.)
+        {
+            return Err(DubinsError::PathParametrisationError);
+        }
+        let path = bindings::DubinsPath {
+            qi: start.to_configuration(),
+            param: [
+                segment_lengths[0] / turning_radius,
+                segment_lengths[1] / turning_radius,
+                segment_lengths[2] / turning_radius,
+            ],
+            rho: turning_radius,
+            type_: shape,
+        };
+        let mut new_path = DubinsPath {
+            inner: Cell::new(path),
+            end: Default::default(),
+        };
+        new_path.end = new_path.sample(new_path.length())?;
+        Ok(new_path)
+    }
+
     pub fn length(&self) -> f64 {
         unsafe { bindings::dubins_path_length(self.inner.as_ptr()) }
     }
@@ -220,6 +318,35 @@ impl DubinsPath {
         unsafe { bindings::dubins_path_type(self.inner.as_ptr()) }
     }
 
+    pub fn turning_radius(&self) -> Metres {
+        self.inner.get().rho
+    }
+
+    /// The segment (0-2) which the given distance along the path falls into.
+    /// Segment boundaries belong to the later segment, as in the C library, except for the end of the path
+    /// which belongs to the last non-empty segment.
+    pub fn segment_at(&self, position: f64) -> u32 {
+        let mut segment_end = 0.;
+        let mut last_nonempty = 0;
+        for segment in 0..3 {
+            let length = self.segment_length(segment);
+            if length > 0. {
+                last_nonempty = segment;
+            }
+            segment_end += length;
+            if position < segment_end {
+                return segment;
+            }
+        }
+        last_nonempty
+    }
+
+    /// Signed curvature of the path at the given distance along it
+    pub fn curvature_at(&self, position: f64) -> f64 {
+        let segment = self.segment_at(position);
+        self.path_type().segment_types()[segment as usize].curvature(self.turning_radius())
+    }
+
     pub fn endpoint(&self) -> Result<OrientedPosition2D, DubinsError> {
         let mut endpoint: Configuration = Default::default();
         unsafe {
@@ -249,6 +376,55 @@ impl DubinsPath {
         Ok(new_path)
     }
 
+    /// Extracts the part of the path after the given distance along it
+    pub fn suffix(&self, start: f64) -> Result<DubinsPath, DubinsError> {
+        let start_pose = self.sample(start)?;
+        let mut path = self.inner.get();
+        path.qi = start_pose.to_configuration();
+        let mut remaining = start / path.rho;
+        for param in path.param.iter_mut() {
+            let cut = remaining.min(*param);
+            *param -= cut;
+            remaining -= cut;
+        }
+        Ok(DubinsPath {
+            inner: Cell::new(path),
+            end: self.end,
+        })
+    }
+
+    /// A path made up of a single segment of the given type
+    fn single_segment(
+        start: OrientedPosition2D,
+        segment: SegmentType,
+        turning_radius: f64,
+        length: f64,
+    ) -> Result<DubinsPath, DubinsError> {
+        let (shape, segment_lengths) = match segment {
+            SegmentType::Left => (DubinsPathType::LSL, [length, 0., 0.]),
+            SegmentType::Straight => (DubinsPathType::LSL, [0., length, 0.]),
+            SegmentType::Right => (DubinsPathType::RSR, [length, 0., 0.]),
+        };
+        DubinsPath::from_segments(start, turning_radius, shape, segment_lengths)
+    }
+
+    pub fn start(&self) -> OrientedPosition2D {
+        self.inner.get().qi.to_oriented_position()
+    }
+
+    /// Moves the path rigidly, by applying `transform` to its start and end configurations
+    fn transformed<F: Fn(OrientedPosition2D) -> OrientedPosition2D>(
+        &self,
+        transform: F,
+    ) -> DubinsPath {
+        let mut path = self.inner.get();
+        path.qi = transform(path.qi.to_oriented_position()).to_configuration();
+        DubinsPath {
+            inner: Cell::new(path),
+            end: transform(self.end),
+        }
+    }
+
     pub fn sample(&self, position: f64) -> Result<OrientedPosition2D, DubinsError> {
         let mut q: Configuration = Default::default();
         unsafe {
@@ -277,15 +453,104 @@ impl DubinsPath {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A single user-supplied waypoint for building a `MultiDubinsPath`.
+/// `path_type` and `speed` apply to the leg which _ends_ at this waypoint, so they are ignored on the first one.
+/// If they are not given, the shortest path and the default speed of the whole path are used.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Waypoint {
+    pub x: Metres,
+    pub y: Metres,
+    /// Anticlockwise rotation
+    pub r: Radians,
+    #[serde(default)]
+    pub path_type: Option<DubinsPathType>,
+    #[serde(default)]
+    pub speed: Option<MetresPerSecond>,
+}
+
+impl Waypoint {
+    pub fn new(x: Metres, y: Metres, r: Radians) -> Self {
+        Waypoint {
+            x,
+            y,
+            r,
+            path_type: None,
+            speed: None,
+        }
+    }
+
+    pub fn pose(&self) -> OrientedPosition2D {
+        OrientedPosition2D::new(self.x, self.y, self.r)
+    }
+}
+
+/// Serialised as the default speed and the list of subpaths (see `DubinsPath`), for example:
+///
+/// ```yaml
+/// speed: 2.0
+/// subpaths:
+///   - start: {x: 0.0, y: 0.0, r: 0.0}
+///     turning_radius: 1.0
+///     word: RSR
+///     segment_lengths: [1.5707963267948966, 2.0, 0.0]
+/// ```
+///
+/// If the subpaths are not all driven at `speed`, a `leg_speeds` list gives the speed of each of them.
+/// Older dumps which also contain `total_length` and `path_lengths` are still accepted.
+#[derive(Debug, Clone)]
 pub struct MultiDubinsPath {
     subpaths: Vec<DubinsPath>,
     speed: MetresPerSecond,
     total_length: Metres,
     path_lengths: Vec<Metres>,
+    /// Per-subpath speeds. Empty if every subpath is driven at `speed`.
+    leg_speeds: Vec<MetresPerSecond>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MultiDubinsPathSpec {
+    speed: MetresPerSecond,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    leg_speeds: Vec<MetresPerSecond>,
+    subpaths: Vec<DubinsPath>,
+}
+
+impl Serialize for MultiDubinsPath {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MultiDubinsPathSpec {
+            speed: self.speed,
+            leg_speeds: self.leg_speeds.clone(),
+            subpaths: self.subpaths.clone(),
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MultiDubinsPath {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let spec = MultiDubinsPathSpec::deserialize(deserializer)?;
+        if spec.subpaths.is_empty() {
+            return Err(D::Error::custom(DubinsError::NotEnoughWaypoints));
+        }
+        if !spec.leg_speeds.is_empty() && spec.leg_speeds.len() != spec.subpaths.len() {
+            return Err(D::Error::custom(
+                "there must be as many leg speeds as there are subpaths",
+            ));
+        }
+        let bad_speed = |speed: MetresPerSecond| speed.is_nan() || speed <= 0.;
+        if bad_speed(spec.speed) || spec.leg_speeds.iter().any(|&speed| bad_speed(speed)) {
+            return Err(D::Error::custom(DubinsError::BadSpeed));
+        }
+        Ok(MultiDubinsPath::from_subpaths(
+            spec.subpaths,
+            spec.speed,
+            spec.leg_speeds,
+        ))
+    }
 }
 
 impl MultiDubinsPath {
+    /// Samples the path every `resolution` seconds along each subpath, with the speed and angular velocity
+    /// calculated analytically from the segment being driven.
     pub fn to_dynamic_trajectory(
         &self,
         resolution: f64,
@@ -294,47 +559,44 @@ impl MultiDubinsPath {
         (Metres2D, Radians),
         (MetresPerSecond, RadiansPerSecond),
     )> {
-        let sampling_resolution = resolution * self.speed; // sample every n metres to achieve sampling every n seconds
-
         self.subpaths
             .iter()
             .enumerate()
             .flat_map(|(index, subpath)| {
+                let speed = self.leg_speed(index);
+                let sampling_resolution = resolution * speed; // sample every n metres to achieve sampling every n seconds
                 // todo dirty hack here, would actually want to bail on first error
                 let data = subpath
                     .to_uniform_data(sampling_resolution)
                     .expect("Could not sample subpath");
-                let x_offset = self.path_lengths.iter().take(index).fold(0., Add::add);
-                data.into_iter()
-                    .map(move |(x, position)| ((x + x_offset) / self.speed, position))
+                let t_offset = self.leg_start_time(index);
+                data.into_iter().map(move |(x, position)| {
+                    (
+                        x / speed + t_offset,
+                        (position.position, position.rotation),
+                        (speed, speed * subpath.curvature_at(x)),
+                    )
+                })
             })
             .chain({
-                let final_time = self.total_length / self.speed;
-                let last_position = self.subpaths.last().unwrap().nominal_end();
-                iter::once((final_time, last_position))
-            })
-            .peekable()
-            .map_with_peek(|this_config, next_config| {
-                let (now_time, now_position) = this_config;
-                let now_movement = if let Some(&(next_time, next_position)) = next_config {
-                    let speed = (next_position.position - now_position.position).length()
-                        / (next_time - now_time);
-                    let omega =
-                        (next_position.rotation - now_position.rotation) / (next_time - now_time);
-                    (speed, omega)
-                } else {
-                    (0., 0.)
-                };
-
-                (
-                    now_time,
-                    (now_position.position, now_position.rotation),
-                    now_movement,
-                )
+                let final_time = self.length();
+                let last_index = self.subpaths.len() - 1;
+                let last_path = &self.subpaths[last_index];
+                let last_position = last_path.nominal_end();
+                let speed = self.leg_speed(last_index);
+                let omega = speed * last_path.curvature_at(last_path.length());
+                iter::once((
+                    final_time,
+                    (last_position.position, last_position.rotation),
+                    (speed, omega),
+                ))
             })
             .collect::<Vec<_>>()
     }
 
+    /// Generates a random path through waypoints within `range` of `origin`.
+    /// If `path_types` is empty each leg takes the shortest path, otherwise each leg uses one of the given path types,
+    /// chosen with probability proportional to its weight from those which can connect the waypoints.
     pub fn generate<R: Rng + ?Sized>(
         turning_radius: Metres,
         speed: MetresPerSecond,
@@ -342,6 +604,7 @@ impl MultiDubinsPath {
         rng: &mut R,
         origin: OrientedPosition2D,
         range: Metres,
+        path_types: &[(DubinsPathType, f64)],
     ) -> Result<Self, DubinsError> {
         let min_distance = min_length * speed;
         let mut subpaths: Vec<DubinsPath> = Vec::with_capacity(1);
@@ -349,45 +612,195 @@ impl MultiDubinsPath {
         let mut end_config = origin;
         while current_distance < min_distance {
             let start_config = end_config;
-            end_config = Self::random_config(rng, range, origin.position);
-            let subpath = DubinsPath::new_shortest(start_config, end_config, turning_radius)?;
+            let mut attempts = 0;
+            let subpath = loop {
+                end_config = Self::random_config(rng, range, origin.position);
+                let subpath = if path_types.is_empty() {
+                    Some(DubinsPath::new_shortest(
+                        start_config,
+                        end_config,
+                        turning_radius,
+                    )?)
+                } else {
+                    Self::random_typed_path(
+                        rng,
+                        start_config,
+                        end_config,
+                        turning_radius,
+                        path_types,
+                    )
+                };
+                if let Some(subpath) = subpath {
+                    break subpath;
+                }
+                attempts += 1;
+                if attempts >= MAX_GENERATION_ATTEMPTS {
+                    return Err(DubinsError::NoPath);
+                }
+            };
             current_distance += subpath.length();
             subpaths.push(subpath);
         }
+
+        Ok(Self::from_subpaths(subpaths, speed, Vec::new()))
+    }
+
+    /// Picks a weighted random path type out of those that can connect `start` to `end`.
+    /// Returns None if none of them can.
+    fn random_typed_path<R: Rng + ?Sized>(
+        rng: &mut R,
+        start: OrientedPosition2D,
+        end: OrientedPosition2D,
+        turning_radius: Metres,
+        path_types: &[(DubinsPathType, f64)],
+    ) -> Option<DubinsPath> {
+        let candidates: Vec<(DubinsPath, f64)> = path_types
+            .iter()
+            .filter(|&&(_, weight)| weight > 0.)
+            .filter_map(|&(shape, weight)| {
+                DubinsPath::with_type(start, end, turning_radius, shape)
+                    .ok()
+                    .map(|path| (path, weight))
+            })
+            .collect();
+        let total_weight: f64 = candidates.iter().map(|&(_, weight)| weight).sum();
+        if candidates.is_empty() {
+            return None;
+        }
+        let mut choice = rng.gen::<f64>() * total_weight;
+        for (path, weight) in candidates.iter() {
+            if choice < *weight {
+                return Some(path.clone());
+            }
+            choice -= weight;
+        }
+        candidates.last().map(|(path, _)| path.clone())
+    }
+
+    /// The path type of each subpath, in order
+    pub fn words(&self) -> Vec<DubinsPathType> {
+        self.subpaths.iter().map(|path| path.path_type()).collect()
+    }
+
+    /// Builds a path which passes through each of the `waypoints` in turn.
+    /// Legs without an explicit path type take the shortest path, and legs without an explicit speed are driven at `speed`.
+    pub fn from_waypoints(
+        waypoints: &[Waypoint],
+        turning_radius: Metres,
+        speed: MetresPerSecond,
+    ) -> Result<Self, DubinsError> {
+        if waypoints.len() < 2 {
+            return Err(DubinsError::NotEnoughWaypoints);
+        }
+        if speed.is_nan() || speed <= 0. {
+            return Err(DubinsError::BadSpeed);
+        }
+
+        let mut subpaths: Vec<DubinsPath> = Vec::with_capacity(waypoints.len() - 1);
+        let mut leg_speeds: Vec<MetresPerSecond> = Vec::with_capacity(waypoints.len() - 1);
+        for leg in waypoints.windows(2) {
+            let (start, end) = (leg[0].pose(), leg[1].pose());
+            let subpath = match leg[1].path_type {
+                Some(shape) => DubinsPath::with_type(start, end, turning_radius, shape)?,
+                None => DubinsPath::new_shortest(start, end, turning_radius)?,
+            };
+            let leg_speed = leg[1].speed.unwrap_or(speed);
+            if leg_speed.is_nan() || leg_speed <= 0. {
+                return Err(DubinsError::BadSpeed);
+            }
+            subpaths.push(subpath);
+            leg_speeds.push(leg_speed);
+        }
+
+        Ok(Self::from_subpaths(subpaths, speed, leg_speeds))
+    }
+
+    fn from_subpaths(
+        subpaths: Vec<DubinsPath>,
+        speed: MetresPerSecond,
+        mut leg_speeds: Vec<MetresPerSecond>,
+    ) -> Self {
+        // don't bother storing the per-leg speeds if they are all the same
+        if leg_speeds.iter().all(|&s| s == speed) {
+            leg_speeds.clear();
+        }
         let path_lengths: Vec<Metres> = subpaths.iter().map(|subpath| subpath.length()).collect();
         let total_length = path_lengths.iter().fold(0., Add::add);
 
-        Ok(MultiDubinsPath {
+        MultiDubinsPath {
             subpaths,
             speed,
             total_length,
             path_lengths,
-        })
+            leg_speeds,
+        }
+    }
+
+    /// The speed at which the subpath with the given index is driven
+    pub fn leg_speed(&self, index: usize) -> MetresPerSecond {
+        self.leg_speeds.get(index).cloned().unwrap_or(self.speed)
+    }
+
+    fn all_leg_speeds(&self) -> Vec<MetresPerSecond> {
+        (0..self.subpaths.len())
+            .map(|index| self.leg_speed(index))
+            .collect()
+    }
+
+    /// The time at which the subpath with the given index is started
+    fn leg_start_time(&self, index: usize) -> Seconds {
+        self.path_lengths
+            .iter()
+            .take(index)
+            .enumerate()
+            .map(|(i, length)| length / self.leg_speed(i))
+            .fold(0., Add::add)
     }
 
     pub fn length(&self) -> Seconds {
-        self.total_length / self.speed
+        self.leg_start_time(self.subpaths.len())
     }
 
-    pub fn sample(&self, t: Seconds) -> Result<(Metres2D, Radians), DubinsError> {
-        let mut sampling_distance = t * self.speed;
-        if sampling_distance > self.total_length {
+    /// Finds the subpath being driven at time `t`, and the distance along it
+    fn locate(&self, t: Seconds) -> Result<(usize, Metres), DubinsError> {
+        if t < 0. || t > self.length() {
             return Err(DubinsError::PathParametrisationError);
         }
 
-        let mut sample: (Metres2D, Radians) = Default::default();
-        for path in self.subpaths.iter() {
-            if path.length() < sampling_distance {
-                sampling_distance -= path.length();
+        let mut remaining_time = t;
+        for (index, path) in self.subpaths.iter().enumerate() {
+            let speed = self.leg_speed(index);
+            let path_time = path.length() / speed;
+            if path_time < remaining_time && index + 1 < self.subpaths.len() {
+                remaining_time -= path_time;
                 continue;
             }
 
-            let o_pos = path.sample(sampling_distance)?;
-            sample = (o_pos.position, o_pos.rotation);
-            break;
+            return Ok((index, (remaining_time * speed).min(path.length())));
         }
 
-        Ok(sample)
+        Err(DubinsError::PathParametrisationError)
+    }
+
+    pub fn sample(&self, t: Seconds) -> Result<(Metres2D, Radians), DubinsError> {
+        let (index, position) = self.locate(t)?;
+        let o_pos = self.subpaths[index].sample(position)?;
+        Ok((o_pos.position, o_pos.rotation))
+    }
+
+    /// Samples the pose at time `t`, together with the speed and angular velocity of the vehicle
+    pub fn sample_dynamics(
+        &self,
+        t: Seconds,
+    ) -> Result<((Metres2D, Radians), (MetresPerSecond, RadiansPerSecond)), DubinsError> {
+        let (index, position) = self.locate(t)?;
+        let path = &self.subpaths[index];
+        let o_pos = path.sample(position)?;
+        let speed = self.leg_speed(index);
+        Ok((
+            (o_pos.position, o_pos.rotation),
+            (speed, speed * path.curvature_at(position)),
+        ))
     }
 
     pub fn endpoint(&self) -> (Metres2D, Radians) {
@@ -400,6 +813,153 @@ impl MultiDubinsPath {
         }
     }
 
+    pub fn startpoint(&self) -> (Metres2D, Radians) {
+        match self.subpaths.first() {
+            None => (Metres2D::zero(), PI / 2.),
+            Some(path) => {
+                let startpoint = path.start();
+                (startpoint.position, startpoint.rotation)
+            }
+        }
+    }
+
+    /// Appends `other` to this path, moving it rigidly so that it starts where this path ends
+    pub fn concat(&self, other: &MultiDubinsPath) -> MultiDubinsPath {
+        let (end_pos, end_rot) = self.endpoint();
+        let (start_pos, start_rot) = other.startpoint();
+        let moved = other
+            .translate(end_pos - start_pos)
+            .rotate(end_rot - start_rot, end_pos);
+
+        let mut subpaths = self.subpaths.clone();
+        subpaths.extend(moved.subpaths);
+        let mut leg_speeds = self.all_leg_speeds();
+        leg_speeds.extend(other.all_leg_speeds());
+        Self::from_subpaths(subpaths, self.speed, leg_speeds)
+    }
+
+    /// Extracts the part of the path driven between the times `start` and `end`
+    pub fn subpath(&self, start: Seconds, end: Seconds) -> Result<MultiDubinsPath, DubinsError> {
+        if start.is_nan() || end.is_nan() || start >= end {
+            return Err(DubinsError::PathParametrisationError);
+        }
+        let (first, first_position) = self.locate(start)?;
+        let (last, last_position) = self.locate(end)?;
+
+        let mut subpaths: Vec<DubinsPath> = Vec::with_capacity(last - first + 1);
+        let mut leg_speeds: Vec<MetresPerSecond> = Vec::with_capacity(last - first + 1);
+        for index in first..last + 1 {
+            let path = &self.subpaths[index];
+            let from = if index == first { first_position } else { 0. };
+            let to = if index == last {
+                last_position
+            } else {
+                path.length()
+            };
+            if to.is_nan() || to <= from {
+                continue;
+            }
+
+            let piece = if from > 0. {
+                path.suffix(from)?
+            } else {
+                path.clone()
+            };
+            let piece = if to < path.length() {
+                piece.subpath(to - from)?
+            } else {
+                piece
+            };
+            subpaths.push(piece);
+            leg_speeds.push(self.leg_speed(index));
+        }
+
+        Ok(Self::from_subpaths(subpaths, self.speed, leg_speeds))
+    }
+
+    pub fn translate(&self, offset: Metres2D) -> MultiDubinsPath {
+        self.transformed(|pose| OrientedPosition2D {
+            position: pose.position + offset,
+            rotation: pose.rotation,
+        })
+    }
+
+    /// Rotates the path anticlockwise by `angle` about `centre`
+    pub fn rotate(&self, angle: Radians, centre: Metres2D) -> MultiDubinsPath {
+        self.transformed(|pose| {
+            let mut polar = (pose.position - centre).to_polar();
+            polar.theta += angle;
+            OrientedPosition2D {
+                position: centre + polar.to_cartesian(),
+                rotation: pose.rotation + angle,
+            }
+        })
+    }
+
+    fn transformed<F: Fn(OrientedPosition2D) -> OrientedPosition2D>(
+        &self,
+        transform: F,
+    ) -> MultiDubinsPath {
+        MultiDubinsPath {
+            subpaths: self
+                .subpaths
+                .iter()
+                .map(|path| path.transformed(&transform))
+                .collect(),
+            speed: self.speed,
+            total_length: self.total_length,
+            path_lengths: self.path_lengths.clone(),
+            leg_speeds: self.leg_speeds.clone(),
+        }
+    }
+
+    /// The path traced by a point `lateral` metres to the left of the vehicle (negative is to the right).
+    /// The legs are timed so that the point stays level with the vehicle, so this gives the ideal trajectory of a
+    /// follower keeping a fixed sideways distance.
+    /// Fails if the point would have to pass through the centre of one of the turns.
+    pub fn offset(&self, lateral: Metres) -> Result<MultiDubinsPath, DubinsError> {
+        let mut subpaths: Vec<DubinsPath> = Vec::with_capacity(self.subpaths.len() * 3);
+        let mut leg_speeds: Vec<MetresPerSecond> = Vec::with_capacity(self.subpaths.len() * 3);
+        for (index, path) in self.subpaths.iter().enumerate() {
+            let speed = self.leg_speed(index);
+            let turning_radius = path.turning_radius();
+            let mut position = 0.;
+            for (segment, segment_type) in path.path_type().segment_types().iter().enumerate() {
+                let length = path.segment_length(segment as u32);
+                if length.is_nan() || length <= 0. {
+                    continue;
+                }
+                let start = path.sample(position)?;
+                position += length;
+
+                let radius = match *segment_type {
+                    SegmentType::Left => turning_radius - lateral,
+                    SegmentType::Straight => turning_radius,
+                    SegmentType::Right => turning_radius + lateral,
+                };
+                let scale = radius / turning_radius;
+                let (length, speed) = match *segment_type {
+                    SegmentType::Straight => (length, speed),
+                    _ => (length * scale, speed * scale),
+                };
+                let offset_start = OrientedPosition2D {
+                    position: start.position
+                        + PolarMetres2D::new(lateral, start.rotation + PI / 2.).to_cartesian(),
+                    rotation: start.rotation,
+                };
+                subpaths.push(DubinsPath::single_segment(
+                    offset_start,
+                    *segment_type,
+                    radius,
+                    length,
+                )?);
+                leg_speeds.push(speed);
+            }
+        }
+
+        Ok(Self::from_subpaths(subpaths, self.speed, leg_speeds))
+    }
+
     fn random_config<R: Rng + ?Sized>(
         rng: &mut R,
         range: Metres,
@@ -441,9 +1001,258 @@ mod tests {
     fn multi_dubins() {
         let mut rng = thread_rng();
         let origin = OrientedPosition2D::new(0., 0., PI / 2.);
-        let multi = MultiDubinsPath::generate(1., 2., 15., &mut rng, origin, 10.)
+        let multi = MultiDubinsPath::generate(1., 2., 15., &mut rng, origin, 10., &[])
             .expect("could not generate");
         let data = multi.to_dynamic_trajectory(0.5);
         println!("{:?}", data);
     }
+
+    #[test]
+    fn multi_dubins_from_waypoints() {
+        let mut second = Waypoint::new(10., 0., 0.);
+        second.speed = Some(1.);
+        let mut third = Waypoint::new(10., 10., PI);
+        third.path_type = Some(DubinsPathType::LSL);
+        let waypoints = vec![Waypoint::new(0., 0., 0.), second, third];
+        let multi = MultiDubinsPath::from_waypoints(&waypoints, 1., 2.)
+            .expect("could not build path from waypoints");
+
+        // the first leg is a straight line driven at 1m/s
+        let (pos, rot) = multi.sample(5.).expect("could not sample path");
+        assert!((pos.x - 5.).abs() < 1e-6);
+        assert!(pos.y.abs() < 1e-6);
+        assert!(rot.abs() < 1e-6);
+
+        let second_leg =
+            DubinsPath::with_type(second.pose(), third.pose(), 1., DubinsPathType::LSL).unwrap();
+        assert!((multi.length() - (10. + second_leg.length() / 2.)).abs() < 1e-6);
+
+        let (end_pos, end_rot) = multi.endpoint();
+        assert_eq!((end_pos.x, end_pos.y, end_rot), (10., 10., PI));
+
+        assert!(MultiDubinsPath::from_waypoints(&waypoints[..1], 1., 2.).is_err());
+        assert!(MultiDubinsPath::from_waypoints(&waypoints, 1., 0.).is_err());
+    }
+
+    /// Quarter right turn starting along the x axis, followed by 2m going straight down
+    fn right_turn_path(speed: f64) -> MultiDubinsPath {
+        let mut end = Waypoint::new(1., -3., 3. * PI / 2.);
+        end.path_type = Some(DubinsPathType::RSR);
+        MultiDubinsPath::from_waypoints(&[Waypoint::new(0., 0., 0.), end], 1., speed)
+            .expect("could not build path")
+    }
+
+    #[test]
+    fn dynamic_trajectory_heading_wrap_around() {
+        let multi = right_turn_path(2.);
+        let data = multi.to_dynamic_trajectory(1. / 64.);
+
+        // the heading wraps from 0 to 2pi straight away, which should not show up in the angular velocity
+        assert!(data.iter().any(|&(_, (_, r), _)| r > PI));
+        for &(t, _, (v, w)) in data.iter() {
+            assert_eq!(v, 2.);
+            if t < PI / 4. {
+                assert!((w + 2.).abs() < 1e-9, "w={} at t={}", w, t);
+            } else {
+                assert!(w.abs() < 1e-9, "w={} at t={}", w, t);
+            }
+        }
+    }
+
+    #[test]
+    fn sample_dynamics_segment_boundaries() {
+        let multi = right_turn_path(2.);
+        let turn_end = PI / 4.;
+        let eps = 1e-6;
+
+        let ((_, r), (v, w)) = multi.sample_dynamics(turn_end - eps).unwrap();
+        assert!((r - 3. * PI / 2.).abs() < 1e-3);
+        assert_eq!((v, w), (2., -2.));
+
+        let (_, (v, w)) = multi.sample_dynamics(turn_end + eps).unwrap();
+        assert_eq!((v, w), (2., 0.));
+
+        // the path is still being driven at its very end
+        let ((pos, _), (v, w)) = multi.sample_dynamics(multi.length()).unwrap();
+        assert!((pos.x - 1.).abs() < 1e-6 && (pos.y + 3.).abs() < 1e-6);
+        assert_eq!((v, w), (2., 0.));
+
+        assert!(multi.sample_dynamics(multi.length() + eps).is_err());
+        assert!(multi.sample_dynamics(-eps).is_err());
+    }
+
+    fn assert_close(a: (Metres2D, Radians), b: (Metres2D, Radians)) {
+        let heading_diff = (a.1 - b.1).sin().abs();
+        assert!(
+            (a.0 - b.0).length() < 1e-6 && heading_diff < 1e-6,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn multi_dubins_concat() {
+        let first = right_turn_path(2.);
+        let second = right_turn_path(1.);
+        let joined = first.concat(&second);
+        assert!((joined.length() - (first.length() + second.length())).abs() < 1e-9);
+
+        // the second path starts off where the first one ended, pointing down
+        let t = first.length() + PI / 2.;
+        let expected = (Metres2D::new(0., -4.), PI);
+        assert_close(joined.sample(t).unwrap(), expected);
+        let (_, (v, w)) = joined.sample_dynamics(t - 0.01).unwrap();
+        assert_eq!((v, w), (1., -1.));
+    }
+
+    #[test]
+    fn multi_dubins_subpath() {
+        let multi = right_turn_path(2.).concat(&right_turn_path(2.));
+        let (start, end) = (0.5, multi.length() - 0.5);
+        let window = multi.subpath(start, end).expect("could not take subpath");
+        assert!((window.length() - (end - start)).abs() < 1e-9);
+        for &t in [0., 0.3, 1., 1.5, window.length()].iter() {
+            assert_close(window.sample(t).unwrap(), multi.sample(t + start).unwrap());
+        }
+        assert!(multi.subpath(1., 1.).is_err());
+        assert!(multi.subpath(0., multi.length() + 1.).is_err());
+    }
+
+    #[test]
+    fn multi_dubins_translate_rotate() {
+        let multi = right_turn_path(2.);
+        let moved = multi
+            .translate(Metres2D::new(1., 1.))
+            .rotate(PI / 2., Metres2D::new(1., 1.));
+        assert_close(moved.startpoint(), (Metres2D::new(1., 1.), PI / 2.));
+        assert_close(moved.endpoint(), (Metres2D::new(4., 2.), 0.));
+        assert_close(moved.sample(1.).unwrap(), {
+            let (pos, rot) = multi.sample(1.).unwrap();
+            (Metres2D::new(1. - pos.y, 1. + pos.x), rot + PI / 2.)
+        });
+    }
+
+    #[test]
+    fn multi_dubins_offset() {
+        let multi = right_turn_path(2.);
+        for &lateral in [0.5, -0.5].iter() {
+            let offset = multi.offset(lateral).expect("could not offset path");
+            assert!((offset.length() - multi.length()).abs() < 1e-9);
+            let mut t = 0.;
+            while t < multi.length() {
+                let (pos, rot) = multi.sample(t).unwrap();
+                let expected = pos + PolarMetres2D::new(lateral, rot + PI / 2.).to_cartesian();
+                assert_close(offset.sample(t).unwrap(), (expected, rot));
+                t += 0.1;
+            }
+        }
+        // can't offset further than the turning radius to the inside of a turn
+        assert!(multi.offset(-1.5).is_err());
+    }
+
+    #[test]
+    fn waypoints_from_yaml() {
+        let yaml = "- {x: 0, y: 0, r: 0}\n- {x: 5, y: 5, r: 1.5, path_type: LSR, speed: 0.5}\n";
+        let waypoints: Vec<Waypoint> = ::serde_yaml::from_str(yaml).expect("could not parse");
+        assert_eq!(waypoints.len(), 2);
+        assert!(waypoints[0].path_type.is_none() && waypoints[0].speed.is_none());
+        match waypoints[1].path_type {
+            Some(DubinsPathType::LSR) => {}
+            other => panic!("unexpected path type {:?}", other),
+        }
+        assert_eq!(waypoints[1].speed, Some(0.5));
+    }
+
+    #[test]
+    fn multi_dubins_yaml_round_trip() {
+        let mut multi = right_turn_path(2.);
+        multi = multi.concat(&right_turn_path(1.));
+        let yaml = ::serde_yaml::to_string(&multi).expect("could not serialise");
+        assert!(yaml.contains("word: RSR"));
+        assert!(!yaml.contains("qi"));
+        let parsed: MultiDubinsPath = ::serde_yaml::from_str(&yaml).expect("could not parse");
+        assert!((parsed.length() - multi.length()).abs() < 1e-9);
+        let mut t = 0.;
+        while t < multi.length() {
+            assert_close(parsed.sample(t).unwrap(), multi.sample(t).unwrap());
+            t += 0.1;
+        }
+    }
+
+    #[test]
+    fn windowed_yaml_round_trip() {
+        let waypoints = [
+            Waypoint::new(0., 0., 0.),
+            Waypoint::new(4., 3., PI / 2.),
+            Waypoint::new(-2., 5., PI),
+            Waypoint::new(-3., -2., -PI / 2.),
+        ];
+        let multi = MultiDubinsPath::from_waypoints(&waypoints, 1., 2.).unwrap();
+        let round_trip = |path: &MultiDubinsPath| {
+            let yaml = ::serde_yaml::to_string(path).expect("could not serialise");
+            let parsed: MultiDubinsPath = ::serde_yaml::from_str(&yaml).expect("could not parse");
+            assert!((parsed.length() - path.length()).abs() < 1e-9);
+            let mut t = 0.;
+            while t < path.length() {
+                assert_close(parsed.sample(t).unwrap(), path.sample(t).unwrap());
+                t += 0.1;
+            }
+        };
+        // windows within single segments have empty segments, which must not come back as full circles
+        round_trip(&right_turn_path(2.).subpath(0., 0.3).unwrap());
+        let duration = multi.length() / 2.;
+        let mut start = 0.;
+        while start < duration - 0.5 {
+            round_trip(&multi.subpath(start, (start + 0.5).min(duration)).unwrap());
+            round_trip(&multi.subpath(start, duration).unwrap());
+            start += 0.35;
+        }
+        round_trip(&multi.offset(0.4).unwrap());
+        round_trip(&multi.offset(-0.4).unwrap());
+    }
+
+    #[test]
+    fn multi_dubins_legacy_yaml() {
+        let yaml = "---
+subpaths:
+  - inner:
+      qi: [0.0, 0.0, 0.0]
+      param: [1.5707963267948966, 2.0, 0.0]
+      rho: 1.0
+      type_: RSR
+    end:
+      x: 1.0
+      y: -3.0
+      r: 4.71238898038469
+speed: 2.0
+total_length: 3.5707963267948966
+path_lengths: [3.5707963267948966]
+";
+        let parsed: MultiDubinsPath = ::serde_yaml::from_str(yaml).expect("could not parse");
+        let expected = right_turn_path(2.);
+        assert!((parsed.length() - expected.length()).abs() < 1e-9);
+        assert_close(parsed.sample(1.).unwrap(), expected.sample(1.).unwrap());
+
+        let bad = "speed: 2.0\nsubpaths:\n  - {start: {x: 0, y: 0, r: 0}, turning_radius: 0, word: LSL, segment_lengths: [1, 0, 0]}\n";
+        assert!(::serde_yaml::from_str::<MultiDubinsPath>(bad).is_err());
+        // segment lengths which don't take the path to the given end
+        let inconsistent = "speed: 2.0\nsubpaths:\n  - {start: {x: 0, y: 0, r: 0}, end: {x: 1, y: 0, r: 0}, turning_radius: 1, word: LSL, segment_lengths: [0, 5, 0]}\n";
+        assert!(::serde_yaml::from_str::<MultiDubinsPath>(inconsistent).is_err());
+        let consistent = inconsistent.replace("[0, 5, 0]", "[0, 1, 0]");
+        assert!(::serde_yaml::from_str::<MultiDubinsPath>(&consistent).is_ok());
+    }
+
+    #[test]
+    fn generate_with_path_types() {
+        let mut rng = thread_rng();
+        let origin = OrientedPosition2D::new(0., 0., PI / 2.);
+        let path_types = [(DubinsPathType::LSR, 1.), (DubinsPathType::RSL, 2.)];
+        let multi = MultiDubinsPath::generate(1., 2., 30., &mut rng, origin, 10., &path_types)
+            .expect("could not generate");
+        assert!(!multi.words().is_empty());
+        for word in multi.words() {
+            assert!(word == DubinsPathType::LSR || word == DubinsPathType::RSL);
+        }
+    }
 }
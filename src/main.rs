@@ -27,6 +27,7 @@ extern crate specs_derive;
 extern crate core;
 extern crate nalgebra;
 
+pub mod augmentation;
 pub mod base;
 pub mod dubins;
 pub mod simulation;
@@ -80,6 +81,7 @@ fn main() {
                         .takes_value(true)
                         .help("Sets rsd of the trajectory"),
                 )
+                .args(&pattern_args(&trajectory::Pattern::names_1d()))
                 .arg(
                     Arg::with_name("num")
                         .short("n")
@@ -128,6 +130,7 @@ fn main() {
                         .takes_value(true)
                         .help("Sets turnability of the trajectory"),
                 )
+                .args(&pattern_args(trajectory::Pattern::names()))
                 .arg(
                     Arg::with_name("num")
                         .short("n")
@@ -195,7 +198,7 @@ fn main() {
                         .long("num-trajectories")
                         .takes_value(true)
                         .help("How many trajectories to generate")
-                        .required(true),
+                        .required_unless("waypoints"),
                 )
                 .arg(
                     Arg::with_name("length")
@@ -210,7 +213,7 @@ fn main() {
                         .long("arena-size")
                         .takes_value(true)
                         .help("Sets size of the arena")
-                        .required(true),
+                        .required_unless("waypoints"),
                 )
                 .arg(
                     Arg::with_name("speed")
@@ -235,6 +238,13 @@ fn main() {
                         .takes_value(true)
                         .help("Resolution at which the trajectory is produced"),
                 )
+                .arg(
+                    Arg::with_name("waypoints")
+                        .short("w")
+                        .long("waypoints")
+                        .takes_value(true)
+                        .help("YAML or CSV file of waypoints to build the trajectory from"),
+                )
                 .arg(
                     Arg::with_name("output_dir")
                         .short("o")
@@ -371,7 +381,7 @@ fn main() {
             let rsd = m.value_of("rsd").map_or(0.1, |s| s.parse::<f64>().unwrap());
             let num = m.value_of("num").unwrap().parse::<usize>().unwrap();
             let out = m.value_of("output_dir").unwrap();
-            traj_gen(length, variability, rsd, num, out);
+            traj_gen(length, variability, rsd, pattern_from_args(m), num, out);
         }
         ("gen-traj-2d", Some(m)) => {
             let length = m
@@ -386,7 +396,15 @@ fn main() {
             let turnability = m
                 .value_of("turnability")
                 .map_or(1., |s| s.parse::<f64>().unwrap());
-            traj_gen_2d(length, variability, rsd, turnability, num, out);
+            traj_gen_2d(
+                length,
+                variability,
+                rsd,
+                turnability,
+                pattern_from_args(m),
+                num,
+                out,
+            );
         }
         ("gen-data", Some(m)) => {
             let length = m
@@ -444,8 +462,15 @@ fn main() {
                 .value_of("length")
                 .map_or(10., |s| s.parse::<f64>().unwrap());
             let out = m.value_of("output_dir").unwrap();
-            let num = m.value_of("num").unwrap().parse::<usize>().unwrap();
-            let arena_size = m.value_of("arena_size").unwrap().parse::<f64>().unwrap();
+            let num = m
+                .value_of("num")
+                .map_or(1, |s| s.parse::<usize>().unwrap());
+            let arena_size = m
+                .value_of("arena_size")
+                .map_or(0., |s| s.parse::<f64>().unwrap());
+            let waypoints = m
+                .value_of("waypoints")
+                .map(|file| tasks::load_waypoints(file).unwrap());
             let speed = m.value_of("speed").unwrap().parse::<f64>().unwrap();
             let turning_radius = m
                 .value_of("turning_radius")
@@ -463,6 +488,7 @@ fn main() {
                 speed,
                 turning_radius,
                 resolution,
+                waypoints,
                 out,
             );
         }
@@ -477,12 +503,16 @@ fn dubins_gen(
     speed: f64,
     turning_radius: f64,
     resolution: f64,
+    waypoints: Option<Vec<dubins::Waypoint>>,
     out: &str,
 ) {
     let num_len = num.to_string().len();
     let out_dir_path = Path::new(out);
     std::fs::create_dir_all(out_dir_path).unwrap();
     let mut rng = thread_rng();
+    let waypoint_trajectory = waypoints.map(|waypoints| {
+        dubins::MultiDubinsPath::from_waypoints(&waypoints, turning_radius, speed).unwrap()
+    });
     let mut all_trajs: Vec<dubins::MultiDubinsPath> = Vec::with_capacity(num);
     for i in 0..num {
         print!(
@@ -491,14 +521,18 @@ fn dubins_gen(
             num,
             width = num_len
         );
-        let trajectory = dubins::MultiDubinsPath::generate(
-            turning_radius,
-            speed,
-            length,
-            &mut rng,
-            OrientedPosition2D::new(0., 0., PI / 2.),
-            arena_size,
-        ).unwrap();
+        let trajectory = match waypoint_trajectory {
+            Some(ref trajectory) => trajectory.clone(),
+            None => dubins::MultiDubinsPath::generate(
+                turning_radius,
+                speed,
+                length,
+                &mut rng,
+                OrientedPosition2D::new(0., 0., PI / 2.),
+                arena_size,
+                &[],
+            ).unwrap(),
+        };
         all_trajs.push(trajectory.clone());
         let data = trajectory.to_dynamic_trajectory(resolution);
 
@@ -527,7 +561,62 @@ fn dubins_gen(
     println!("\nDone!");
 }
 
-fn traj_gen(length: f64, variability: f64, rsd: f64, num: usize, out: &str) {
+/// Options for generating pattern trajectories instead of random ones, out of the patterns in `names`
+fn pattern_args<'a, 'b>(names: &[&'b str]) -> Vec<Arg<'a, 'b>> {
+    let number = |name: &'a str, help: &'b str| {
+        Arg::with_name(name)
+            .long(name)
+            .takes_value(true)
+            .requires("pattern")
+            .help(help)
+    };
+    vec![
+        Arg::with_name("pattern")
+            .long("pattern")
+            .takes_value(true)
+            .possible_values(names)
+            .help("Generates the given deterministic pattern instead of random trajectories"),
+        number("period", "Sets the period of the pattern, in seconds"),
+        number("amplitude", "Sets the amplitude of the pattern"),
+        number("phase", "Sets the starting phase of the pattern in radians"),
+        number("radius", "Sets the radius of the pattern"),
+        number("ratio", "Sets the frequency ratio of a lissajous pattern"),
+        number("resolution", "Sets the time between points of the pattern"),
+    ]
+}
+
+/// Returns the chosen pattern, its parameters and the resolution to sample it at
+fn pattern_from_args(
+    m: &clap::ArgMatches,
+) -> Option<(trajectory::Pattern, trajectory::PatternParams, Seconds)> {
+    let pattern = trajectory::Pattern::from_name(m.value_of("pattern")?)?;
+    let default = trajectory::PatternParams::default();
+    let get = |name: &str, default: f64| {
+        m.value_of(name)
+            .map_or(default, |s| s.parse::<f64>().unwrap())
+    };
+    let params = trajectory::PatternParams {
+        period: get("period", default.period),
+        amplitude: get("amplitude", default.amplitude),
+        phase: get("phase", default.phase),
+        radius: get("radius", default.radius),
+        ratio: get("ratio", default.ratio),
+    };
+    if let Err(err) = pattern.validate(&params) {
+        eprintln!("An error has occurred: {}", err);
+        ::std::process::exit(1);
+    }
+    Some((pattern, params, get("resolution", 0.1)))
+}
+
+fn traj_gen(
+    length: f64,
+    variability: f64,
+    rsd: f64,
+    pattern: Option<(trajectory::Pattern, trajectory::PatternParams, Seconds)>,
+    num: usize,
+    out: &str,
+) {
     let max_speed = 0.5;
     let num_len = num.to_string().len();
     let out_dir_path = Path::new(out);
@@ -540,8 +629,23 @@ fn traj_gen(length: f64, variability: f64, rsd: f64, num: usize, out: &str) {
             num,
             width = num_len
         );
-        let trajectory =
-            trajectory::generate_1d_trajectory_points_simple(max_speed, length, variability, rsd);
+        let trajectory = match pattern {
+            Some((pattern, ref params, resolution)) => {
+                trajectory::generate_1d_trajectory_points_pattern(
+                    pattern, params, length, resolution,
+                ).unwrap_or_else(|err| {
+                    eprintln!("An error has occurred: {}", err);
+                    ::std::process::exit(1);
+                })
+            }
+            None => trajectory::generate_1d_trajectory_points_simple(
+                max_speed,
+                length,
+                variability,
+                rsd,
+                &Default::default(),
+            ),
+        };
         all_trajs.push(trajectory.clone());
 
         // write to a test path
@@ -561,12 +665,20 @@ fn traj_gen(length: f64, variability: f64, rsd: f64, num: usize, out: &str) {
     println!("\nDone!");
 }
 
-fn traj_gen_2d(length: f64, variability: f64, rsd: f64, turnability: f64, num: usize, out: &str) {
+fn traj_gen_2d(
+    length: f64,
+    variability: f64,
+    rsd: f64,
+    turnability: f64,
+    pattern: Option<(trajectory::Pattern, trajectory::PatternParams, Seconds)>,
+    num: usize,
+    out: &str,
+) {
     let max_speed = 0.5;
     let num_len = num.to_string().len();
     let out_dir_path = Path::new(out);
     std::fs::create_dir_all(out_dir_path).unwrap();
-    let mut all_trajs: Vec<Vec<(Seconds, Metres2D)>> = Vec::with_capacity(num);
+    let mut all_trajs: Vec<Vec<(Seconds, Metres2D, Radians)>> = Vec::with_capacity(num);
     for i in 0..num {
         print!(
             "\rWorking... [{:0width$}/{:0width$}]",
@@ -574,23 +686,43 @@ fn traj_gen_2d(length: f64, variability: f64, rsd: f64, turnability: f64, num: u
             num,
             width = num_len
         );
-        let trajectory = trajectory::generate_2d_trajectory_points_simple(
-            max_speed,
-            length,
-            variability,
-            rsd,
-            turnability,
-        );
+        let trajectory = match pattern {
+            // patterns are headed in their direction of motion
+            Some((pattern, ref params, resolution)) => {
+                let points = trajectory::generate_2d_trajectory_points_pattern(
+                    pattern, params, length, resolution,
+                );
+                let naive = trajectory::NaiveTrajectory::from_points(resolution, points);
+                trajectory::Heading2DTrajectory::from(naive)
+                    .data()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &(pos, heading))| (i as f64 * resolution, pos, heading))
+                    .collect()
+            }
+            None => trajectory::generate_2d_trajectory_points_with_heading(
+                max_speed,
+                length,
+                variability,
+                rsd,
+                turnability,
+                &Default::default(),
+            ),
+        };
         all_trajs.push(trajectory.clone());
 
         // write to a test path
         let file_name = format!("traj_{:0width$}.csv", i, width = num_len);
         let mut writer = csv::Writer::from_path(out_dir_path.join(&file_name)).unwrap();
-        writer.write_record(&["t", "x", "y"]).unwrap();
-        for &(t, r) in trajectory.iter() {
+        writer.write_record(&["t", "x", "y", "heading"]).unwrap();
+        for &(t, r, heading) in trajectory.iter() {
             writer
-                .write_record(&[t.to_string(), r.x.to_string(), r.y.to_string()])
-                .unwrap();
+                .write_record(&[
+                    t.to_string(),
+                    r.x.to_string(),
+                    r.y.to_string(),
+                    heading.to_string(),
+                ]).unwrap();
         }
         writer.flush().unwrap();
     }
@@ -618,8 +750,13 @@ fn tf_data_gen(
             num,
             width = num_len
         );
-        let trajectory =
-            trajectory::generate_1d_trajectory_points_simple(max_speed, length, variability, rsd);
+        let trajectory = trajectory::generate_1d_trajectory_points_simple(
+            max_speed,
+            length,
+            variability,
+            rsd,
+            &Default::default(),
+        );
         let resolution = 1. / 10.;
         let trajectory_mode = simulation::LeaderTrajectoryMode::Follow;
         let converted_trajectory = trajectory::NaiveTrajectory::from_points(resolution, trajectory);
@@ -653,10 +790,15 @@ fn tf_data_gen(
             &converted_trajectory,
             trajectory_mode,
         );
-        let mut observer = simulation::SimpleObserver::new(0.05);
-        //let mut observer = simulation::PerfectObserver {};
-        let result0 = simulation0.run(length, resolution, &mut observer);
-        let result1 = simulation1.run(length, resolution, &mut observer);
+        // each robot in each simulation has its own observer
+        let observers = || {
+            (0..2)
+                .map(|_| simulation::SimpleObserver::new(0.05))
+                .collect::<Vec<_>>()
+        };
+        //let observers = || vec![simulation::PerfectObserver {}; 2];
+        let result0 = simulation0.run(length, resolution, observers());
+        let result1 = simulation1.run(length, resolution, observers());
 
         writer.write_record(result0, 0)?;
         writer.write_record(result1, 1)?;
@@ -680,8 +822,13 @@ fn data_gen(length: f64, variability: f64, rsd: f64, num: usize, out: &str) {
             num,
             width = num_len
         );
-        let trajectory =
-            trajectory::generate_1d_trajectory_points_simple(max_speed, length, variability, rsd);
+        let trajectory = trajectory::generate_1d_trajectory_points_simple(
+            max_speed,
+            length,
+            variability,
+            rsd,
+            &Default::default(),
+        );
         let resolution = 1. / 10.;
         let trajectory_mode = simulation::LeaderTrajectoryMode::Follow;
         let converted_trajectory = trajectory::NaiveTrajectory::from_points(resolution, trajectory);
@@ -714,14 +861,10 @@ fn data_gen(length: f64, variability: f64, rsd: f64, num: usize, out: &str) {
             &converted_trajectory,
             trajectory_mode,
         );
-        //        let mut observer = simulation::SimpleObserver::new(0.1);
-        let mut observer = simulation::PerfectObserver {};
-        let result0 = simulation0
-            .run(length, resolution, &mut observer)
-            .into_data();
-        let result1 = simulation1
-            .run(length, resolution, &mut observer)
-            .into_data();
+        //        let observers = || (0..2).map(|_| simulation::SimpleObserver::new(0.1)).collect::<Vec<_>>();
+        let observers = || vec![simulation::PerfectObserver {}; 2];
+        let result0 = simulation0.run(length, resolution, observers()).into_data();
+        let result1 = simulation1.run(length, resolution, observers()).into_data();
 
         let file_name = format!("traj_{:0width$}_l0.csv", i, width = num_len);
         let mut writer = csv::Writer::from_path(out_dir_path.join(&file_name)).unwrap();
@@ -767,6 +910,7 @@ fn data_gen_2d(length: f64, variability: f64, rsd: f64, turnability: f64, num: u
             variability,
             rsd,
             turnability,
+            &Default::default(),
         );
         let resolution = 1. / 10.;
         let trajectory_mode = simulation::LeaderTrajectoryMode::Follow;
@@ -804,14 +948,10 @@ fn data_gen_2d(length: f64, variability: f64, rsd: f64, turnability: f64, num: u
             &converted_trajectory,
             trajectory_mode,
         );
-        //let mut observer = simulation::SimpleObserver::new(0.05);
-        let mut observer = simulation::PerfectObserver {};
-        let result0 = simulation0
-            .run(length, resolution, &mut observer)
-            .into_data();
-        let result1 = simulation1
-            .run(length, resolution, &mut observer)
-            .into_data();
+        //let observers = || (0..2).map(|_| simulation::SimpleObserver::new(0.05)).collect::<Vec<_>>();
+        let observers = || vec![simulation::PerfectObserver {}; 2];
+        let result0 = simulation0.run(length, resolution, observers()).into_data();
+        let result1 = simulation1.run(length, resolution, observers()).into_data();
 
         let file_name = format!("traj_{:0width$}_l0.csv", i, width = num_len);
         let mut writer = csv::Writer::from_path(out_dir_path.join(&file_name)).unwrap();
@@ -895,10 +1035,10 @@ fn test_traj_gen() {
         &converted_trajectory,
         trajectory_mode,
     );
-    //let mut observer = simulation::SimpleObserver::new(0.1);
-    let mut observer = simulation::PerfectObserver {};
+    //let observers = (0..2).map(|_| simulation::SimpleObserver::new(0.1)).collect::<Vec<_>>();
+    let observers = vec![simulation::PerfectObserver {}; 2];
 
-    let result = simulation.run(10., resolution, &mut observer).into_data();
+    let result = simulation.run(10., resolution, observers).into_data();
 
     // write to a test path
     let mut writer = csv::Writer::from_path("../test_data/test_traj.csv").unwrap();
@@ -1,9 +1,11 @@
 use base::*;
+use num::Zero;
 use pid_control::{Controller as PIDControllerT, PIDController as PIDControllerImpl};
 use rand::distributions::StandardNormal;
 use rand::rngs::SmallRng;
 use rand::{FromEntropy, Rng};
-use trajectory::NaiveTrajectory;
+use std::marker::PhantomData;
+use trajectory::{NaiveTrajectory, UniformResolutionTrajectory};
 
 const LIMIT_FACTOR: f64 = 2.;
 
@@ -15,6 +17,54 @@ pub trait Controller<S: Vector>: Clone {
     fn take_step(&mut self, distance: S, time_step: Seconds) -> S;
 }
 
+/// Object-safe counterpart of `Controller`, so robots in one simulation can use different controllers
+pub trait DynController<S: Vector> {
+    fn dyn_target(&self) -> S;
+    fn dyn_set_target(&mut self, target: S);
+    fn dyn_take_step(&mut self, distance: S, time_step: Seconds) -> S;
+    fn clone_box(&self) -> Box<dyn DynController<S>>;
+}
+
+pub type BoxedController<S> = Box<dyn DynController<S>>;
+
+impl<S: Vector, C: Controller<S> + 'static> DynController<S> for C {
+    fn dyn_target(&self) -> S {
+        self.target()
+    }
+
+    fn dyn_set_target(&mut self, target: S) {
+        self.set_target(target)
+    }
+
+    fn dyn_take_step(&mut self, distance: S, time_step: Seconds) -> S {
+        self.take_step(distance, time_step)
+    }
+
+    fn clone_box(&self) -> Box<dyn DynController<S>> {
+        Box::new(self.clone())
+    }
+}
+
+impl<S: Vector + 'static> Clone for Box<dyn DynController<S>> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+impl<S: Vector + 'static> Controller<S> for Box<dyn DynController<S>> {
+    fn target(&self) -> S {
+        (**self).dyn_target()
+    }
+
+    fn set_target(&mut self, target: S) {
+        (**self).dyn_set_target(target)
+    }
+
+    fn take_step(&mut self, distance: S, time_step: Seconds) -> S {
+        (**self).dyn_take_step(distance, time_step)
+    }
+}
+
 /// A simplified view of a formation for N robots. Some robot is defined to be the leader and
 /// all others attempt to keep their distance from it as defined by the formation.
 /// The origin specifies their initial positions in a simulation.
@@ -31,18 +81,76 @@ pub trait SimulationResult<S: Vector> {
     fn num_steps(&self) -> usize;
     fn path_error(&self) -> Option<f64>;
 
+    /// The heading of each robot at each step, if they were recorded.
+    /// First dimension is robot number, second dimension is step
+    fn headings(&self) -> Option<&Vec<Vec<Radians>>> {
+        None
+    }
+
+    /// Other vectors recorded for each robot at each step
+    fn channels(&self) -> &[Channel<S>] {
+        &[]
+    }
+
     /// First dimension is robot number, second dimension is step
     fn into_data(self) -> Vec<Vec<S>>;
 }
 
+/// What the vectors in a `Channel` are, which decides how they are transformed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChannelKind {
+    Position,
+    Displacement,
+    /// Rates of change, which turn around when time is reversed
+    Rate,
+}
+
+/// Vectors recorded for each robot alongside its position, such as its sensor readings.
+/// Steps at which a robot has no value are NaN.
+#[derive(Debug, Clone)]
+pub struct Channel<S: Vector> {
+    pub name: &'static str,
+    pub kind: ChannelKind,
+    /// First dimension is robot number, second dimension is step
+    pub data: Vec<Vec<S>>,
+}
+
+impl<S: Vector> Channel<S> {
+    pub fn new(name: &'static str, kind: ChannelKind, num_robots: usize) -> Self {
+        Channel {
+            name,
+            kind,
+            data: vec![Vec::new(); num_robots],
+        }
+    }
+}
+
+/// Which channels a simulation records besides the observed positions
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    /// Position of each robot without observation errors, as "true_pos"
+    #[serde(default)]
+    pub true_position: bool,
+    /// Velocity of each robot over the previous step, including disturbances, as "v"
+    #[serde(default)]
+    pub velocity: bool,
+    /// Sensor reading of the offset to each robot's target, as "sensed"
+    #[serde(default)]
+    pub sensed: bool,
+    /// Velocity commanded by each robot's controller, as "command"
+    #[serde(default)]
+    pub command: bool,
+}
+
 pub trait Simulation<S: Vector> {
     type Result: SimulationResult<S>;
 
-    fn run(
+    /// `observers` has one observer per robot, so each robot is observed independently
+    fn run<O: Observer<S>>(
         self,
         total_time: Seconds,
         time_step: Seconds,
-        observer: &mut Observer<S>,
+        observers: Vec<O>,
     ) -> Self::Result;
 }
 
@@ -225,8 +333,197 @@ impl Controller<Metres2D> for UniformPIDController2D {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct PIDGains {
+    pub p_gain: f64,
+    pub i_gain: f64,
+    pub d_gain: f64,
+}
+
+/// How a saturating controller stops its integral term growing while its output is limited
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum AntiWindup {
+    /// Integrate regardless of saturation
+    None,
+    /// Limit the integral term to the maximum speed
+    Clamp,
+    /// Stop integrating while saturated, unless integrating would reduce the output
+    ConditionalIntegration,
+    /// Feed the excess over the maximum speed back into the integral term, scaled by `gain`
+    BackCalculation { gain: f64 },
+}
+
+impl Default for AntiWindup {
+    fn default() -> Self {
+        AntiWindup::Clamp
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PIDController2DParams {
+    pub x: PIDGains,
+    pub y: PIDGains,
+    /// Limit on the magnitude of the output velocity
+    pub max_speed: MetresPerSecond,
+    pub anti_windup: AntiWindup,
+}
+
+/// PID controller with separate gains for each axis, which saturates the velocity vector as a whole.
+/// The derivative term acts on the measurement, as in `pid_control`.
+#[derive(Debug, Clone)]
+pub struct PIDController2D {
+    params: PIDController2DParams,
+    target: Metres2D,
+    integral: Metres2D,
+    previous_distance: Option<Metres2D>,
+}
+
+impl PIDController2D {
+    pub fn new(params: PIDController2DParams) -> Self {
+        PIDController2D {
+            params,
+            target: Metres2D::zero(),
+            integral: Metres2D::zero(),
+            previous_distance: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.integral = Metres2D::zero();
+        self.previous_distance = None;
+    }
+
+    fn saturate(&self, velocity: Metres2D) -> Metres2D {
+        let speed = velocity.length();
+        if speed > self.params.max_speed {
+            velocity * (self.params.max_speed / speed)
+        } else {
+            velocity
+        }
+    }
+}
+
+impl Controller<Metres2D> for PIDController2D {
+    fn target(&self) -> Metres2D {
+        self.target
+    }
+
+    fn set_target(&mut self, target: Metres2D) {
+        self.target = target;
+    }
+
+    fn take_step(&mut self, distance: Metres2D, time_step: Seconds) -> Metres2DPerSecond {
+        let PIDController2DParams {
+            x,
+            y,
+            max_speed,
+            anti_windup,
+        } = self.params;
+        let error = self.target - distance;
+        let p_term = Metres2D::new(x.p_gain * error.x, y.p_gain * error.y);
+        let d_term = match self.previous_distance {
+            Some(previous) => Metres2D::new(
+                x.d_gain * (previous.x - distance.x) / time_step,
+                y.d_gain * (previous.y - distance.y) / time_step,
+            ),
+            None => Metres2D::zero(),
+        };
+        self.previous_distance = Some(distance);
+        let increment = Metres2D::new(x.i_gain * error.x, y.i_gain * error.y) * time_step;
+
+        match anti_windup {
+            AntiWindup::None => self.integral += increment,
+            AntiWindup::Clamp => {
+                self.integral += increment;
+                let integral_size = self.integral.length();
+                if integral_size > max_speed {
+                    self.integral *= max_speed / integral_size;
+                }
+            }
+            AntiWindup::ConditionalIntegration => {
+                let output = p_term + self.integral + d_term;
+                if output.length() <= max_speed || increment.dot(&output) < 0. {
+                    self.integral += increment;
+                }
+            }
+            AntiWindup::BackCalculation { gain } => {
+                self.integral += increment;
+                let output = p_term + self.integral + d_term;
+                self.integral += (self.saturate(output) - output) * (gain * time_step);
+            }
+        }
+
+        self.saturate(p_term + self.integral + d_term)
+    }
+}
+
 #[derive(Debug, Clone)]
-pub struct SimpleSimulationResult<S: Vector>(Seconds, Vec<Vec<S>>, Option<f64>);
+pub struct UniformPIDController3D {
+    controller_x: PIDControllerImpl,
+    controller_y: PIDControllerImpl,
+    controller_z: PIDControllerImpl,
+    params: PIDControllerParams,
+}
+
+impl UniformPIDController3D {
+    pub fn new(params: PIDControllerParams) -> Self {
+        let new_controller = || {
+            let mut controller =
+                PIDControllerImpl::new(params.p_gain, params.i_gain, params.d_gain);
+            controller.set_limits(
+                LIMIT_FACTOR * params.vel_limits.0,
+                LIMIT_FACTOR * params.vel_limits.1,
+            );
+            controller
+        };
+        UniformPIDController3D {
+            controller_x: new_controller(),
+            controller_y: new_controller(),
+            controller_z: new_controller(),
+            params,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.controller_x.reset();
+        self.controller_y.reset();
+        self.controller_z.reset();
+    }
+}
+
+impl Controller<Metres3D> for UniformPIDController3D {
+    fn target(&self) -> Metres3D {
+        Metres3D {
+            x: self.controller_x.target(),
+            y: self.controller_y.target(),
+            z: self.controller_z.target(),
+        }
+    }
+
+    fn set_target(&mut self, target: Metres3D) {
+        self.controller_x.set_target(target.x);
+        self.controller_y.set_target(target.y);
+        self.controller_z.set_target(target.z);
+    }
+
+    fn take_step(&mut self, distance: Metres3D, time_step: Seconds) -> Metres3DPerSecond {
+        Metres3D {
+            x: self.controller_x.update(distance.x, time_step),
+            y: self.controller_y.update(distance.y, time_step),
+            z: self.controller_z.update(distance.z, time_step),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SimpleSimulationResult<S: Vector>(
+    Seconds,
+    Vec<Vec<S>>,
+    Option<f64>,
+    Option<Vec<Vec<Radians>>>,
+    Vec<Channel<S>>,
+);
 
 impl<S: Vector> SimulationResult<S> for SimpleSimulationResult<S> {
     fn time_step(&self) -> Seconds {
@@ -249,6 +546,14 @@ impl<S: Vector> SimulationResult<S> for SimpleSimulationResult<S> {
         self.2
     }
 
+    fn headings(&self) -> Option<&Vec<Vec<Radians>>> {
+        self.3.as_ref()
+    }
+
+    fn channels(&self) -> &[Channel<S>] {
+        &self.4
+    }
+
     fn into_data(self) -> Vec<Vec<S>> {
         self.1
     }
@@ -270,10 +575,129 @@ impl Default for LeaderTrajectoryMode {
     }
 }
 
+/// Which robot each follower keeps its formation position relative to
+#[derive(PartialEq, Copy, Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum Topology {
+    /// Every follower follows the leader
+    Star,
+
+    /// Robots follow their neighbour in the formation on the side of the leader
+    Chain,
+
+    /// A tree rooted at the leader, filled breadth first with the followers in formation order,
+    /// where each robot has up to `branching` followers
+    Tree { branching: usize },
+
+    /// Each follower follows the nearest robot in the initial formation which connects it to the leader,
+    /// building a minimum spanning tree out from the leader
+    Nearest,
+}
+
+impl Default for Topology {
+    fn default() -> Self {
+        Topology::Star
+    }
+}
+
+impl Topology {
+    /// The robot each robot follows, with the leader following itself
+    pub fn parents<S: Vector>(&self, positions: &[S], leader_id: usize) -> Vec<usize> {
+        let num_robots = positions.len();
+        assert!(leader_id < num_robots);
+        match *self {
+            Topology::Star => vec![leader_id; num_robots],
+            Topology::Chain => (0..num_robots)
+                .map(|id| {
+                    if id < leader_id {
+                        id + 1
+                    } else if id > leader_id {
+                        id - 1
+                    } else {
+                        id
+                    }
+                })
+                .collect(),
+            Topology::Tree { branching } => {
+                assert!(branching > 0, "trees must branch at least once");
+                let order: Vec<usize> = Some(leader_id)
+                    .into_iter()
+                    .chain((0..num_robots).filter(|&id| id != leader_id))
+                    .collect();
+                let mut parents = vec![leader_id; num_robots];
+                for (place, &id) in order.iter().enumerate().skip(1) {
+                    parents[id] = order[(place - 1) / branching];
+                }
+                parents
+            }
+            Topology::Nearest => {
+                let mut parents = vec![leader_id; num_robots];
+                let mut connected = vec![false; num_robots];
+                connected[leader_id] = true;
+                for _ in 1..num_robots {
+                    let mut nearest: Option<(f64, usize, usize)> = None;
+                    for from in (0..num_robots).filter(|&id| connected[id]) {
+                        for to in (0..num_robots).filter(|&id| !connected[id]) {
+                            let distance = (positions[to] - positions[from]).length().abs();
+                            if nearest.map_or(true, |(best, _, _)| distance < best) {
+                                nearest = Some((distance, from, to));
+                            }
+                        }
+                    }
+                    let (_, from, to) = nearest.unwrap();
+                    parents[to] = from;
+                    connected[to] = true;
+                }
+                parents
+            }
+        }
+    }
+}
+
 pub trait DistanceSensor<S>: Clone {
     fn sense(&mut self, true_d: S) -> S;
 }
 
+/// Object-safe counterpart of `DistanceSensor`, so robots in one simulation can use different sensors
+pub trait DynDistanceSensor<S> {
+    fn dyn_sense(&mut self, true_d: S) -> S;
+    fn clone_box(&self) -> Box<dyn DynDistanceSensor<S>>;
+}
+
+pub type BoxedDistanceSensor<S> = Box<dyn DynDistanceSensor<S>>;
+
+impl<S, Se: DistanceSensor<S> + 'static> DynDistanceSensor<S> for Se {
+    fn dyn_sense(&mut self, true_d: S) -> S {
+        self.sense(true_d)
+    }
+
+    fn clone_box(&self) -> Box<dyn DynDistanceSensor<S>> {
+        Box::new(self.clone())
+    }
+}
+
+impl<S: 'static> Clone for Box<dyn DynDistanceSensor<S>> {
+    fn clone(&self) -> Self {
+        (**self).clone_box()
+    }
+}
+
+impl<S: 'static> DistanceSensor<S> for Box<dyn DynDistanceSensor<S>> {
+    fn sense(&mut self, true_d: S) -> S {
+        (**self).dyn_sense(true_d)
+    }
+}
+
+/// Senses distances exactly
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PerfectSensor;
+
+impl<S> DistanceSensor<S> for PerfectSensor {
+    fn sense(&mut self, true_d: S) -> S {
+        true_d
+    }
+}
+
 pub struct SharpIrSensor {
     rng: SmallRng,
 }
@@ -309,7 +733,7 @@ impl DistanceSensor<f64> for SharpIrSensor {
         const SHARP_IR_EQN: [f64; 3] = [-0.020077009250469, 0.120573832696841, 0.003295559781587];
         let d2 = true_d * true_d;
         let d3 = true_d * d2;
-        let sd = d3 * SHARP_IR_EQN[0] + d2 * SHARP_IR_EQN[1] + true_d * SHARP_IR_EQN[0];
+        let sd = d3 * SHARP_IR_EQN[0] + d2 * SHARP_IR_EQN[1] + true_d * SHARP_IR_EQN[2];
         let error = self.rng.sample(StandardNormal) * sd;
         true_d + error
     }
@@ -342,18 +766,310 @@ impl Clone for CombinedIrEncoderSensor {
     }
 }
 
+const ENCODER_ANGLE_SD: f64 = 0.004363323 / 1.96;
+
 impl DistanceSensor<Metres2D> for CombinedIrEncoderSensor {
     fn sense(&mut self, true_d: Metres2D) -> Metres2D {
-        const ANGLE_SD: f64 = 0.004363323 / 1.96;
         let mut polar = true_d.to_polar();
         // apply distance noise
         polar.r = self.distance_sensor.sense(polar.r);
         // apply angle noise
-        polar.theta += self.rng.sample(StandardNormal) * ANGLE_SD;
+        polar.theta += self.rng.sample(StandardNormal) * ENCODER_ANGLE_SD;
         polar.to_cartesian()
     }
 }
 
+/// The sensor is mounted on a pan-tilt head, so both the azimuth and elevation are measured by encoders
+impl DistanceSensor<Metres3D> for CombinedIrEncoderSensor {
+    fn sense(&mut self, true_d: Metres3D) -> Metres3D {
+        let horizontal = true_d.horizontal();
+        let r = self.distance_sensor.sense(true_d.length());
+        let azimuth = horizontal.angle() + self.rng.sample(StandardNormal) * ENCODER_ANGLE_SD;
+        let elevation = true_d.z.atan2(horizontal.length())
+            + self.rng.sample(StandardNormal) * ENCODER_ANGLE_SD;
+        Metres3D::from_horizontal(
+            PolarMetres2D::new(r * elevation.cos(), azimuth).to_cartesian(),
+            r * elevation.sin(),
+        )
+    }
+}
+
+/// Standard deviation of the range noise, as a function of the true range
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum RangeNoise {
+    /// Sum of `coefficients[i] * range^i`
+    Polynomial { coefficients: Vec<f64> },
+    /// Interpolated linearly between `(range, sd)` points in increasing order of range,
+    /// and constant beyond the first and last points
+    Table { points: Vec<(Metres, f64)> },
+}
+
+impl RangeNoise {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if let RangeNoise::Table { ref points } = *self {
+            if points.is_empty() {
+                return Err("noise tables need at least one point");
+            }
+            if points.windows(2).any(|pair| pair[1].0 <= pair[0].0) {
+                return Err("noise table ranges must be increasing");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn sd(&self, range: Metres) -> f64 {
+        let sd = match *self {
+            RangeNoise::Polynomial { ref coefficients } => coefficients
+                .iter()
+                .rev()
+                .fold(0., |total, coefficient| total * range + coefficient),
+            RangeNoise::Table { ref points } => {
+                let above = points
+                    .iter()
+                    .position(|&(point_range, _)| point_range > range);
+                match above {
+                    Some(0) => points[0].1,
+                    Some(i) => {
+                        let (r0, sd0) = points[i - 1];
+                        let (r1, sd1) = points[i];
+                        sd0 + (sd1 - sd0) * (range - r0) / (r1 - r0)
+                    }
+                    None => points[points.len() - 1].1,
+                }
+            }
+        };
+        sd.max(0.)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangeSensorParams {
+    pub noise: RangeNoise,
+    /// Standard deviation of the noise on each measured angle, in 2 and 3 dimensions
+    #[serde(default)]
+    pub angle_sd: Radians,
+    /// Ranges beyond this read as the maximum range
+    #[serde(default)]
+    pub max_range: Option<Metres>,
+    /// Probability of a reading being dropped, in which case the previous reading is repeated
+    #[serde(default)]
+    pub dropout: f64,
+    /// Ranges are rounded to a multiple of this
+    #[serde(default)]
+    pub quantisation: Option<Metres>,
+    /// Added to every range
+    #[serde(default)]
+    pub bias: Metres,
+}
+
+impl RangeSensorParams {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        self.noise.validate()?;
+        if self.angle_sd.is_nan() || self.angle_sd < 0. {
+            return Err("angle standard deviation must not be negative");
+        }
+        if self.dropout.is_nan() || self.dropout < 0. || self.dropout >= 1. {
+            return Err("dropout probability must be at least 0 and less than 1");
+        }
+        if let Some(range) = self.max_range {
+            if range.is_nan() || range <= 0. {
+                return Err("maximum range must be positive");
+            }
+        }
+        if let Some(step) = self.quantisation {
+            if step.is_nan() || step <= 0. {
+                return Err("quantisation step must be positive");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Measures the range to the target with configurable noise, and the bearing with encoders in 2 and 3 dimensions
+pub struct RangeSensor<S> {
+    params: RangeSensorParams,
+    rng: SmallRng,
+    previous: Option<S>,
+}
+
+impl<S> RangeSensor<S> {
+    pub fn new(params: RangeSensorParams) -> RangeSensor<S> {
+        RangeSensor {
+            params,
+            rng: SmallRng::from_entropy(),
+            previous: None,
+        }
+    }
+
+    fn range(&mut self, true_range: Metres) -> Metres {
+        let params = &self.params;
+        let noise = self.rng.sample(StandardNormal) * params.noise.sd(true_range);
+        let mut range = true_range + params.bias + noise;
+        if let Some(step) = params.quantisation {
+            range = (range / step).round() * step;
+        }
+        if let Some(max_range) = params.max_range {
+            range = range.min(max_range);
+        }
+        range.max(0.)
+    }
+
+    fn angle(&mut self, true_angle: Radians) -> Radians {
+        true_angle + self.rng.sample(StandardNormal) * self.params.angle_sd
+    }
+
+    /// Repeats the previous reading if this one is dropped
+    fn reading(&mut self, reading: S) -> S
+    where
+        S: Copy,
+    {
+        let dropped = self.previous.is_some() && self.rng.gen_bool(self.params.dropout);
+        match self.previous {
+            Some(previous) if dropped => previous,
+            _ => {
+                self.previous = Some(reading);
+                reading
+            }
+        }
+    }
+}
+
+impl<S> Clone for RangeSensor<S> {
+    fn clone(&self) -> Self {
+        // Seed the rng anew
+        Self::new(self.params.clone())
+    }
+}
+
+impl DistanceSensor<Metres> for RangeSensor<Metres> {
+    fn sense(&mut self, true_d: Metres) -> Metres {
+        let range = self.range(true_d.abs());
+        self.reading(range * if true_d < 0. { -1. } else { 1. })
+    }
+}
+
+impl DistanceSensor<Metres2D> for RangeSensor<Metres2D> {
+    fn sense(&mut self, true_d: Metres2D) -> Metres2D {
+        let polar = true_d.to_polar();
+        let range = self.range(polar.r);
+        let theta = self.angle(polar.theta);
+        self.reading(PolarMetres2D::new(range, theta).to_cartesian())
+    }
+}
+
+impl DistanceSensor<Metres3D> for RangeSensor<Metres3D> {
+    fn sense(&mut self, true_d: Metres3D) -> Metres3D {
+        let horizontal = true_d.horizontal();
+        let range = self.range(true_d.length());
+        let azimuth = self.angle(horizontal.angle());
+        let elevation = self.angle(true_d.z.atan2(horizontal.length()));
+        self.reading(Metres3D::from_horizontal(
+            PolarMetres2D::new(range * elevation.cos(), azimuth).to_cartesian(),
+            range * elevation.sin(),
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct KalmanFilterParams {
+    /// Standard deviation of the target's acceleration along each axis, in m/s^2
+    pub process_noise: f64,
+    /// Standard deviation of each sensor reading along each axis
+    pub measurement_noise: Metres,
+}
+
+impl KalmanFilterParams {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if self.process_noise.is_nan() || self.process_noise < 0. {
+            return Err("process noise must not be negative");
+        }
+        if self.measurement_noise.is_nan() || self.measurement_noise <= 0. {
+            return Err("measurement noise must be positive");
+        }
+        Ok(())
+    }
+}
+
+/// Estimate of the offset to the target and of the target's velocity along one axis
+#[derive(Debug, Clone)]
+struct KalmanAxis {
+    offset: f64,
+    velocity: f64,
+    covariance: [[f64; 2]; 2],
+}
+
+/// Linear Kalman filter which estimates the offset to a robot's target from its sensor readings.
+/// The target moves at a constant velocity perturbed by random accelerations,
+/// and the robot's own velocity is known.
+#[derive(Debug, Clone)]
+pub struct KalmanFilter<S: Vector> {
+    params: KalmanFilterParams,
+    /// Empty until the first reading
+    axes: Vec<KalmanAxis>,
+    _marker: PhantomData<S>,
+}
+
+impl<S: Vector> KalmanFilter<S> {
+    pub fn new(params: KalmanFilterParams) -> Self {
+        KalmanFilter {
+            params,
+            axes: Vec::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Fuses a sensor reading taken `time_step` after the previous one, during which the robot moved at `velocity`.
+    /// Returns the estimated offset to the target.
+    pub fn update(&mut self, reading: S, velocity: S, time_step: Seconds) -> S {
+        let r = self.params.measurement_noise.powi(2);
+        if self.axes.is_empty() {
+            // every robot starts at rest
+            self.axes = reading
+                .repr()
+                .into_iter()
+                .map(|offset| KalmanAxis {
+                    offset,
+                    velocity: 0.,
+                    covariance: [[r, 0.], [0., 0.]],
+                })
+                .collect();
+            return reading;
+        }
+
+        let dt = time_step;
+        let q = self.params.process_noise.powi(2);
+        let estimate: Vec<f64> = self
+            .axes
+            .iter_mut()
+            .zip(reading.repr().into_iter().zip(velocity.repr()))
+            .map(|(axis, (z, own_velocity))| {
+                // predict, with F = [[1, dt], [0, 1]] and the robot's velocity as the control input
+                let offset = axis.offset + (axis.velocity - own_velocity) * dt;
+                let p = axis.covariance;
+                let p00 =
+                    p[0][0] + dt * (p[0][1] + p[1][0]) + dt * dt * p[1][1] + q * dt.powi(4) / 4.;
+                let p01 = p[0][1] + dt * p[1][1] + q * dt.powi(3) / 2.;
+                let p10 = p[1][0] + dt * p[1][1] + q * dt.powi(3) / 2.;
+                let p11 = p[1][1] + q * dt * dt;
+
+                // update, measuring the offset
+                let innovation = z - offset;
+                let s = p00 + r;
+                let (k0, k1) = (p00 / s, p10 / s);
+                axis.offset = offset + k0 * innovation;
+                axis.velocity += k1 * innovation;
+                axis.covariance = [
+                    [(1. - k0) * p00, (1. - k0) * p01],
+                    [p10 - k1 * p00, p11 - k1 * p01],
+                ];
+                axis.offset
+            })
+            .collect();
+        S::from_repr(&estimate)
+    }
+}
+
 pub trait Observer<S: Vector> {
     fn observe(&mut self, true_pos: S) -> S;
 }
@@ -396,6 +1112,16 @@ impl Observer<Metres2D> for SimpleObserver {
     }
 }
 
+impl Observer<Metres3D> for SimpleObserver {
+    fn observe(&mut self, true_pos: Metres3D) -> Metres3D {
+        let error_x = self.rng.sample(StandardNormal) * self.sd;
+        let error_y = self.rng.sample(StandardNormal) * self.sd;
+        let error_z = self.rng.sample(StandardNormal) * self.sd;
+        true_pos + Metres3D::new(error_x, error_y, error_z)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct PerfectObserver {}
 
 impl<S: Vector> Observer<S> for PerfectObserver {
@@ -404,6 +1130,168 @@ impl<S: Vector> Observer<S> for PerfectObserver {
     }
 }
 
+impl<S: Vector> Observer<S> for Box<dyn Observer<S>> {
+    fn observe(&mut self, true_pos: S) -> S {
+        (**self).observe(true_pos)
+    }
+}
+
+/// Adds the same gaussian noise to every axis
+fn add_noise<S: Vector, R: Rng>(position: S, sd: f64, rng: &mut R) -> S {
+    let noisy: Vec<f64> = position
+        .repr()
+        .into_iter()
+        .map(|value| value + rng.sample(StandardNormal) * sd)
+        .collect();
+    S::from_repr(&noisy)
+}
+
+/// Offsets every observation by the same amount
+pub struct BiasObserver<S: Vector> {
+    offset: S,
+}
+
+impl<S: Vector> BiasObserver<S> {
+    pub fn new(offset: S) -> Self {
+        BiasObserver { offset }
+    }
+}
+
+impl<S: Vector> Observer<S> for BiasObserver<S> {
+    fn observe(&mut self, true_pos: S) -> S {
+        true_pos + self.offset
+    }
+}
+
+/// Offsets observations by a random walk, which takes a step of `step_sd` on each axis at every observation
+pub struct DriftObserver<S: Vector> {
+    step_sd: f64,
+    offset: S,
+    rng: SmallRng,
+}
+
+impl<S: Vector> DriftObserver<S> {
+    pub fn new(step_sd: f64) -> Self {
+        DriftObserver {
+            step_sd,
+            offset: S::zero(),
+            rng: SmallRng::from_entropy(),
+        }
+    }
+}
+
+impl<S: Vector> Observer<S> for DriftObserver<S> {
+    fn observe(&mut self, true_pos: S) -> S {
+        self.offset = add_noise(self.offset, self.step_sd, &mut self.rng);
+        true_pos + self.offset
+    }
+}
+
+/// Occasionally adds a gross error, with standard deviation `sd`, to an observation
+pub struct OutlierObserver {
+    probability: f64,
+    sd: f64,
+    rng: SmallRng,
+}
+
+impl OutlierObserver {
+    pub fn new(probability: f64, sd: f64) -> Self {
+        OutlierObserver {
+            probability,
+            sd,
+            rng: SmallRng::from_entropy(),
+        }
+    }
+}
+
+impl<S: Vector> Observer<S> for OutlierObserver {
+    fn observe(&mut self, true_pos: S) -> S {
+        if self.rng.gen_bool(self.probability) {
+            add_noise(true_pos, self.sd, &mut self.rng)
+        } else {
+            true_pos
+        }
+    }
+}
+
+/// Misses observations with the given probability, observing NaN on every axis instead
+pub struct DropoutObserver {
+    probability: f64,
+    rng: SmallRng,
+}
+
+impl DropoutObserver {
+    pub fn new(probability: f64) -> Self {
+        DropoutObserver {
+            probability,
+            rng: SmallRng::from_entropy(),
+        }
+    }
+}
+
+impl<S: Vector> Observer<S> for DropoutObserver {
+    fn observe(&mut self, true_pos: S) -> S {
+        if self.rng.gen_bool(self.probability) {
+            S::from_repr(&vec![f64::NAN; S::repr_length()])
+        } else {
+            true_pos
+        }
+    }
+}
+
+/// Applies several observers in turn, so their errors add up
+pub struct CombinedObserver<S: Vector> {
+    observers: Vec<Box<dyn Observer<S>>>,
+}
+
+impl<S: Vector> CombinedObserver<S> {
+    pub fn new(observers: Vec<Box<dyn Observer<S>>>) -> Self {
+        CombinedObserver { observers }
+    }
+}
+
+impl<S: Vector> Observer<S> for CombinedObserver<S> {
+    fn observe(&mut self, true_pos: S) -> S {
+        self.observers
+            .iter_mut()
+            .fold(true_pos, |pos, observer| observer.observe(pos))
+    }
+}
+
+/// Whether an observation was made, rather than dropped
+pub fn is_observed<S: Vector>(position: &S) -> bool {
+    position.repr().iter().all(|value| !value.is_nan())
+}
+
+/// Disturbances which push a robot off course, on top of the velocity its controller commands
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Disturbance<S> {
+    /// Speed of a constant wind
+    pub wind: MetresPerSecond,
+    /// Unit vector the wind blows along
+    pub wind_direction: S,
+    /// Standard deviation of gaussian noise added to the velocity along each axis at every step
+    pub process_noise: MetresPerSecond,
+    /// Mean number of pushes per second
+    pub impulse_rate: f64,
+    /// Distance each push moves the robot, in a random direction
+    pub impulse: Metres,
+}
+
+/// A uniformly random unit vector
+pub fn random_direction<S: Vector, R: Rng + ?Sized>(rng: &mut R) -> S {
+    loop {
+        let repr: Vec<f64> = (0..S::repr_length())
+            .map(|_| rng.sample(StandardNormal))
+            .collect();
+        let length = repr.iter().map(|v| v * v).sum::<f64>().sqrt();
+        if length > 1e-9 {
+            let unit: Vec<f64> = repr.into_iter().map(|v| v / length).collect();
+            return S::from_repr(&unit);
+        }
+    }
+}
+
 /// Simple simulation in Metres and Seconds which uses the same controller for every robot
 pub struct SimpleSimulation<S: Vector, C: Controller<S>, Se: DistanceSensor<S>> {
     num_robots: usize,
@@ -413,10 +1301,21 @@ pub struct SimpleSimulation<S: Vector, C: Controller<S>, Se: DistanceSensor<S>>
     current_pos: Vec<S>,
     current_vel: Vec<S>,
     trajectory: NaiveTrajectory<S>,
+    /// Heading of the reference trajectory at each of its samples, if it has one
+    reference_headings: Option<Vec<Radians>>,
     trajectory_origin: S,
+    /// Setpoint of each robot relative to the robot it follows
     targets: Vec<S>,
+    /// Setpoint of each robot relative to the leader, used for the path error
+    leader_offsets: Vec<S>,
+    parents: Vec<usize>,
     follow_mode: LeaderTrajectoryMode,
     sensors: Vec<Se>,
+    with_headings: bool,
+    /// Filters which estimate the offset to each robot's target from its sensor readings, if there are any
+    estimators: Option<Vec<KalmanFilter<S>>>,
+    disturbances: Option<Vec<Disturbance<S>>>,
+    recording: Recording,
 }
 
 impl<C, S, Se> SimpleSimulation<S, C, Se>
@@ -425,15 +1324,21 @@ where
     C: Controller<S>,
     Se: DistanceSensor<S>,
 {
-    pub fn new<CI: IntoIterator<Item = C>, SI: IntoIterator<Item = Se>, F: Formation<S>>(
+    pub fn new<CI, SI, F, T>(
         num_robots: usize,
         leader_id: usize,
         sensors: SI,
         controllers: CI,
         formation: &F,
-        trajectory: &NaiveTrajectory<S>,
+        trajectory: &T,
         follow_mode: LeaderTrajectoryMode,
-    ) -> Self {
+    ) -> Self
+    where
+        CI: IntoIterator<Item = C>,
+        SI: IntoIterator<Item = Se>,
+        F: Formation<S>,
+        T: UniformResolutionTrajectory<S>,
+    {
         let mut controllers: Vec<C> = controllers.into_iter().collect();
         let sensors: Vec<Se> = sensors.into_iter().collect();
         assert!(leader_id < num_robots);
@@ -455,6 +1360,18 @@ where
             c.set_target(*target);
         }
 
+        let naive_trajectory = NaiveTrajectory::from_trajectory(trajectory);
+        let resolution = trajectory.resolution();
+        let reference_headings = trajectory.heading_at_time(0.).map(|_| {
+            (0..naive_trajectory.data().len())
+                .map(|index| {
+                    trajectory
+                        .heading_at_time(index as f64 * resolution)
+                        .unwrap()
+                })
+                .collect()
+        });
+
         SimpleSimulation {
             num_robots,
             leader_id,
@@ -462,11 +1379,87 @@ where
             results: vec![Vec::new(); num_robots],
             current_pos,
             current_vel: vec![S::zero(); num_robots],
-            trajectory: trajectory.clone(),
+            trajectory: naive_trajectory,
+            reference_headings,
             trajectory_origin: leader_pos,
+            leader_offsets: targets.clone(),
             targets,
+            parents: vec![leader_id; num_robots],
             follow_mode,
             sensors,
+            with_headings: false,
+            estimators: None,
+            disturbances: None,
+            recording: Default::default(),
+        }
+    }
+
+    /// Makes each follower keep its formation position relative to the robot given by `topology`,
+    /// rather than the leader
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.parents = topology.parents(&self.current_pos, self.leader_id);
+        for (id, controller) in self.controllers.iter_mut().enumerate() {
+            self.targets[id] = self.current_pos[self.parents[id]] - self.current_pos[id];
+            controller.set_target(self.targets[id]);
+        }
+        self
+    }
+
+    /// The robot each robot follows, with the leader following itself
+    pub fn parents(&self) -> &[usize] {
+        &self.parents
+    }
+
+    /// Also records the heading of each robot, which must be in 2D.
+    /// The leader takes the heading of the reference trajectory if it has one and the leader follows it exactly.
+    /// Otherwise robots face in their direction of motion, keeping their heading while stationary.
+    pub fn record_headings(mut self) -> Self {
+        assert!(S::repr_length() >= 2, "headings need 2D positions");
+        self.with_headings = true;
+        self
+    }
+
+    /// Gives each robot's controller the estimate of a Kalman filter, rather than the raw sensor reading.
+    /// Both are recorded, as the "sensed" and "estimated" channels.
+    pub fn with_estimator(mut self, params: KalmanFilterParams) -> Self {
+        self.estimators = Some(vec![KalmanFilter::new(params); self.num_robots]);
+        self
+    }
+
+    /// Also records the given channels, with NaN for a leader which has no sensor reading or command
+    pub fn with_recording(mut self, recording: Recording) -> Self {
+        self.recording = recording;
+        self
+    }
+
+    /// Pushes each robot off course with its own disturbances.
+    /// A leader which takes the reference trajectory exactly is not disturbed.
+    pub fn with_disturbances(mut self, disturbances: Vec<Disturbance<S>>) -> Self {
+        assert_eq!(disturbances.len(), self.num_robots);
+        self.disturbances = Some(disturbances);
+        self
+    }
+
+    /// How far a disturbance pushes a robot over one step
+    fn push<R: Rng>(disturbance: &Disturbance<S>, time_step: Seconds, rng: &mut R) -> S {
+        let noise: Vec<f64> = (0..S::repr_length())
+            .map(|_| rng.sample(StandardNormal) * disturbance.process_noise)
+            .collect();
+        let wind = disturbance.wind_direction * disturbance.wind;
+        let mut push = (wind + S::from_repr(&noise)) * time_step;
+        if rng.gen_bool((disturbance.impulse_rate * time_step).min(1.)) {
+            push += random_direction::<S, _>(rng) * disturbance.impulse;
+        }
+        push
+    }
+
+    /// Heading of a displacement, or None if it is too small for the heading to be meaningful
+    fn direction(displacement: S) -> Option<Radians> {
+        let repr = displacement.repr();
+        if repr[0].hypot(repr[1]) > 1e-9 {
+            Some(repr[1].atan2(repr[0]))
+        } else {
+            None
         }
     }
 }
@@ -481,13 +1474,14 @@ where
 
     /// Runs the entire simulation synchronously.
     /// Runs for an integer number of `time_step`s, potentially stopping past `total_time` if they are not multiples
-    fn run(
+    fn run<O: Observer<S>>(
         mut self,
         total_time: Seconds,
         time_step: Seconds,
-        observer: &mut Observer<S>,
+        mut observers: Vec<O>,
     ) -> SimpleSimulationResult<S> {
         assert_eq!(self.trajectory.time_step(), time_step);
+        assert_eq!(observers.len(), self.num_robots);
         // +1 since `num_steps` is really the number of data points, and the number of steps is one less than that.
         let num_steps: usize = ((total_time / time_step).ceil() as usize) + 1;
         assert!(self.trajectory.data().len() >= num_steps);
@@ -498,6 +1492,20 @@ where
             res_vec.reserve(num_steps);
         }
 
+        let mut previous_pos = self.current_pos.clone();
+        let mut current_headings: Vec<Option<Radians>> = vec![None; self.num_robots];
+        let mut recorded_headings: Vec<Vec<Option<Radians>>> = vec![Vec::new(); self.num_robots];
+        let recording = self.recording;
+        let record_sensed = recording.sensed || self.estimators.is_some();
+        let mut true_positions = Channel::new("true_pos", ChannelKind::Position, self.num_robots);
+        let mut velocities = Channel::new("v", ChannelKind::Rate, self.num_robots);
+        let mut sensed = Channel::new("sensed", ChannelKind::Displacement, self.num_robots);
+        let mut estimated = Channel::new("estimated", ChannelKind::Displacement, self.num_robots);
+        let mut commands = Channel::new("command", ChannelKind::Rate, self.num_robots);
+        let not_sensed = S::from_repr(&vec![f64::NAN; S::repr_length()]);
+        let mut rng = SmallRng::from_entropy();
+        let disturbances = self.disturbances.take().unwrap_or_default();
+
         for step in 0..num_steps {
             let leader_reference_pos = self.trajectory.data()[step] + self.trajectory_origin;
 
@@ -510,36 +1518,106 @@ where
             // Also, record the current position at this step into the results
             // Vel is kept at 0 in the case of DefinedTrajectory, so this is fine
             // Also add to total path error (don't distinguish different robots)
-            for ((mut pos, vel), mut result) in self
+            // Disturbances push the robots off course as well, except for a leader exactly taking the reference trajectory
+            let pushes: Vec<S> = (0..self.num_robots)
+                .map(|id| match disturbances.get(id) {
+                    Some(disturbance)
+                        if step > 0
+                            && !(id == self.leader_id
+                                && self.follow_mode == LeaderTrajectoryMode::Predefined) =>
+                    {
+                        Self::push(disturbance, time_step, &mut rng)
+                    }
+                    _ => S::zero(),
+                })
+                .collect();
+            for ((((pos, vel), push), result), observer) in self
                 .current_pos
                 .iter_mut()
                 .zip(self.current_vel.iter())
+                .zip(pushes.iter())
                 .zip(self.results.iter_mut())
+                .zip(observers.iter_mut())
             {
-                *pos += *vel * time_step;
+                *pos += *vel * time_step + *push;
                 result.push(observer.observe(*pos));
             }
 
+            let moves = self.current_pos.iter().zip(previous_pos.iter()).enumerate();
+            for (id, (&pos, &previous)) in moves {
+                if recording.true_position {
+                    true_positions.data[id].push(pos);
+                }
+                if recording.velocity {
+                    velocities.data[id].push((pos - previous) / time_step);
+                }
+            }
+
+            if self.with_headings {
+                for id in 0..self.num_robots {
+                    let reference_heading = match self.reference_headings {
+                        Some(ref headings)
+                            if id == self.leader_id
+                                && self.follow_mode == LeaderTrajectoryMode::Predefined =>
+                        {
+                            Some(headings[step])
+                        }
+                        _ => None,
+                    };
+                    let heading = reference_heading
+                        .or_else(|| Self::direction(self.current_pos[id] - previous_pos[id]));
+                    if heading.is_some() {
+                        current_headings[id] = heading;
+                    }
+                    recorded_headings[id].push(current_headings[id]);
+                }
+            }
+            previous_pos.copy_from_slice(&self.current_pos);
+
             // Now run the controllers for each robot, obtaining the new velocity for the next time slice
-            let leader_pos = self.current_pos[self.leader_id];
             for (id, ((mut controller, mut velocity), setpoint_target)) in self
                 .controllers
                 .iter_mut()
                 .zip(self.current_vel.iter_mut())
-                .zip(self.targets.iter())
+                .zip(self.leader_offsets.iter())
                 .enumerate()
             {
                 let target_pos = if id == self.leader_id {
                     match self.follow_mode {
-                        LeaderTrajectoryMode::Predefined => continue,
+                        LeaderTrajectoryMode::Predefined => {
+                            if record_sensed {
+                                sensed.data[id].push(not_sensed);
+                            }
+                            if self.estimators.is_some() {
+                                estimated.data[id].push(not_sensed);
+                            }
+                            if recording.command {
+                                commands.data[id].push(not_sensed);
+                            }
+                            continue;
+                        }
                         LeaderTrajectoryMode::Follow => leader_reference_pos,
                     }
                 } else {
-                    leader_pos
+                    self.current_pos[self.parents[id]]
                 };
                 let target_offset = target_pos - self.current_pos[id];
                 let sensed_distance = self.sensors[id].sense(target_offset);
-                *velocity = controller.take_step(sensed_distance, time_step);
+                if record_sensed {
+                    sensed.data[id].push(sensed_distance);
+                }
+                let distance = match self.estimators {
+                    Some(ref mut estimators) => {
+                        let estimate = estimators[id].update(sensed_distance, *velocity, time_step);
+                        estimated.data[id].push(estimate);
+                        estimate
+                    }
+                    None => sensed_distance,
+                };
+                *velocity = controller.take_step(distance, time_step);
+                if recording.command {
+                    commands.data[id].push(*velocity);
+                }
 
                 // calc position error based on true distance from _trajectory_, not leader
                 // offset from ideal leader
@@ -566,6 +1644,331 @@ where
             }
         };
 
-        SimpleSimulationResult(time_step, self.results, Some(path_error))
+        // Robots which have not moved yet face the way they first move, or the way the reference trajectory starts
+        let initial_heading = self
+            .reference_headings
+            .as_ref()
+            .map_or(0., |headings| headings[0]);
+        let headings = if self.with_headings {
+            let headings = recorded_headings
+                .into_iter()
+                .map(|robot_headings| {
+                    let first_heading = robot_headings
+                        .iter()
+                        .filter_map(|&heading| heading)
+                        .next()
+                        .unwrap_or(initial_heading);
+                    robot_headings
+                        .into_iter()
+                        .map(|heading| heading.unwrap_or(first_heading))
+                        .collect()
+                })
+                .collect();
+            Some(headings)
+        } else {
+            None
+        };
+
+        let channels = vec![
+            (recording.true_position, true_positions),
+            (recording.velocity, velocities),
+            (record_sensed, sensed),
+            (self.estimators.is_some(), estimated),
+            (recording.command, commands),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, channel)| if enabled { Some(channel) } else { None })
+        .collect();
+
+        SimpleSimulationResult(
+            time_step,
+            self.results,
+            Some(path_error),
+            headings,
+            channels,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn topologies() {
+        let positions = [0., 1., 3., 6., 7.];
+        assert_eq!(Topology::Star.parents(&positions, 2), vec![2; 5]);
+        assert_eq!(Topology::Chain.parents(&positions, 2), vec![1, 2, 2, 2, 3]);
+        assert_eq!(
+            Topology::Tree { branching: 2 }.parents(&positions, 2),
+            vec![2, 2, 2, 0, 0]
+        );
+        assert_eq!(
+            Topology::Tree { branching: 1 }.parents(&positions, 0),
+            vec![0, 0, 1, 2, 3]
+        );
+        // robots in a line join up along it
+        assert_eq!(
+            Topology::Nearest.parents(&positions, 4),
+            vec![1, 2, 3, 4, 4]
+        );
+    }
+
+    #[test]
+    fn pid_controller_2d() {
+        let gains = |p_gain, i_gain| PIDGains {
+            p_gain,
+            i_gain,
+            d_gain: 0.,
+        };
+        let params = |x, anti_windup| PIDController2DParams {
+            x,
+            y: gains(10., 0.),
+            max_speed: 1.,
+            anti_windup,
+        };
+
+        // the velocity vector is saturated as a whole
+        let mut controller = PIDController2D::new(params(gains(10., 0.), AntiWindup::None));
+        controller.set_target(Metres2D::new(10., 10.));
+        let velocity = controller.take_step(Metres2D::zero(), 0.1);
+        assert!((velocity.length() - 1.).abs() < 1e-9);
+        assert!((velocity.x - velocity.y).abs() < 1e-9);
+
+        // after winding up, only anti-windup lets the output recover quickly
+        let anti_windups = [
+            AntiWindup::None,
+            AntiWindup::Clamp,
+            AntiWindup::ConditionalIntegration,
+            AntiWindup::BackCalculation { gain: 10. },
+        ];
+        for &anti_windup in anti_windups.iter() {
+            let mut controller = PIDController2D::new(params(gains(0., 1.), anti_windup));
+            controller.set_target(Metres2D::new(10., 0.));
+            for _ in 0..100 {
+                controller.take_step(Metres2D::zero(), 0.1);
+            }
+            controller.set_target(Metres2D::new(-10., 0.));
+            controller.take_step(Metres2D::zero(), 0.1);
+            let velocity = controller.take_step(Metres2D::zero(), 0.1);
+            if anti_windup == AntiWindup::None {
+                assert!((velocity.x - 1.).abs() < 1e-9);
+            } else {
+                assert!(velocity.x < 0.5, "{:?} gave {:?}", anti_windup, velocity);
+            }
+        }
+    }
+
+    #[test]
+    fn range_sensors() {
+        let polynomial = RangeNoise::Polynomial {
+            coefficients: vec![1., 2., 3.],
+        };
+        assert_eq!(polynomial.sd(2.), 17.);
+        let table = RangeNoise::Table {
+            points: vec![(1., 0.1), (3., 0.3)],
+        };
+        assert_eq!(table.sd(0.), 0.1);
+        assert!((table.sd(2.) - 0.2).abs() < 1e-9);
+        assert_eq!(table.sd(5.), 0.3);
+
+        let params = RangeSensorParams {
+            noise: RangeNoise::Polynomial {
+                coefficients: vec![],
+            },
+            angle_sd: 0.,
+            max_range: Some(2.),
+            dropout: 0.,
+            quantisation: Some(0.5),
+            bias: 0.1,
+        };
+        assert!(params.validate().is_ok());
+        let mut sensor = RangeSensor::<Metres>::new(params.clone());
+        assert!((sensor.sense(1.3) - 1.5).abs() < 1e-9);
+        assert!((sensor.sense(-1.3) + 1.5).abs() < 1e-9);
+        assert_eq!(sensor.sense(5.), 2.);
+        let reading = RangeSensor::<Metres2D>::new(params.clone()).sense(Metres2D::new(3., 4.));
+        assert!((reading.x - 1.2).abs() < 1e-9 && (reading.y - 1.6).abs() < 1e-9);
+
+        // dropped readings repeat the previous one
+        let mut dropping = RangeSensor::<Metres>::new(RangeSensorParams {
+            dropout: 1. - 1e-12,
+            ..params.clone()
+        });
+        assert!((dropping.sense(1.) - 1.).abs() < 1e-9);
+        assert!((dropping.sense(0.3) - 1.).abs() < 1e-9);
+
+        let mut bad = params.clone();
+        bad.dropout = 1.;
+        assert!(bad.validate().is_err());
+        bad = params;
+        bad.noise = RangeNoise::Table {
+            points: vec![(3., 0.1), (1., 0.3)],
+        };
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn observers() {
+        let mut bias = BiasObserver::new(Metres2D::new(1., -1.));
+        assert_eq!(bias.observe(Metres2D::new(2., 2.)), Metres2D::new(3., 1.));
+
+        let mut dropout = DropoutObserver::new(1.);
+        let dropped: Metres2D = dropout.observe(Metres2D::new(2., 2.));
+        assert!(dropped.x.is_nan() && dropped.y.is_nan());
+        assert!(!is_observed(&dropped));
+        assert!(is_observed(&Metres2D::new(2., 2.)));
+
+        // errors add up, and a dropped frame stays dropped
+        let mut combined = CombinedObserver::new(vec![
+            Box::new(BiasObserver::new(1.)),
+            Box::new(DriftObserver::new(0.)),
+            Box::new(OutlierObserver::new(0., 10.)),
+            Box::new(BiasObserver::new(2.)),
+        ]);
+        assert_eq!(combined.observe(1.), 4.);
+        let mut combined: CombinedObserver<Metres> = CombinedObserver::new(vec![
+            Box::new(DropoutObserver::new(1.)),
+            Box::new(BiasObserver::new(2.)),
+        ]);
+        assert!(!is_observed(&combined.observe(1.)));
+    }
+
+    #[test]
+    fn kalman_filter() {
+        let params = KalmanFilterParams {
+            process_noise: 0.01,
+            measurement_noise: 0.1,
+        };
+        assert!(params.validate().is_ok());
+        let bad = KalmanFilterParams {
+            measurement_noise: 0.,
+            ..params
+        };
+        assert!(bad.validate().is_err());
+
+        // readings alternately too far and too close are averaged out
+        let mut filter = KalmanFilter::new(params);
+        let mut estimate = 0.;
+        for step in 0..50 {
+            let noise = if step % 2 == 0 { 0.1 } else { -0.1 };
+            estimate = filter.update(1. + noise, 0., 0.1);
+        }
+        assert!((estimate - 1.).abs() < 0.05);
+
+        // a target which sets off at constant speed is tracked, given the robot's own velocity
+        let mut filter = KalmanFilter::new(KalmanFilterParams {
+            process_noise: 1.,
+            ..params
+        });
+        let mut offset = Metres2D::new(1., 0.);
+        for _ in 0..100 {
+            offset += Metres2D::new(0.1, 0.) - Metres2D::new(0., 0.05);
+            estimate = filter.update(offset, Metres2D::new(0., 0.5), 0.1).x;
+        }
+        assert!((estimate - offset.x).abs() < 1e-3);
+    }
+
+    #[test]
+    fn estimator_channels() {
+        let params = KalmanFilterParams {
+            process_noise: 1.,
+            measurement_noise: 0.1,
+        };
+        let trajectory = NaiveTrajectory::from_points(0.1, vec![(0., 0.), (1., 1.)]);
+        let simulation = SimpleSimulation::new(
+            2,
+            0,
+            vec![PerfectSensor, PerfectSensor],
+            vec![PController::new(Default::default()); 2],
+            &SimpleFormation::new(2, 0., vec![1., 0.]),
+            &trajectory,
+            LeaderTrajectoryMode::Predefined,
+        ).with_estimator(params);
+        let result = simulation.run(1., 0.1, vec![PerfectObserver {}; 2]);
+        let channels = result.channels();
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[0].name, "sensed");
+        assert_eq!(channels[1].name, "estimated");
+        for channel in channels.iter() {
+            assert_eq!(channel.data[0].len(), result.num_steps());
+            assert!(channel.data[0][0].is_nan());
+            assert_eq!(channel.data[1].len(), result.num_steps());
+        }
+        assert_eq!(channels[0].data[1][0], 1.);
+    }
+
+    #[test]
+    fn disturbances() {
+        // the follower stays still, apart from being blown by the wind
+        let controller = PController::new(PControllerParams {
+            p_gain: 0.,
+            ..Default::default()
+        });
+        let trajectory = NaiveTrajectory::from_points(0.1, vec![(0., 0.), (1., 1.)]);
+        let wind = Disturbance {
+            wind: 0.5,
+            wind_direction: -1.,
+            ..Default::default()
+        };
+        let simulation = SimpleSimulation::new(
+            2,
+            0,
+            vec![PerfectSensor, PerfectSensor],
+            vec![controller; 2],
+            &SimpleFormation::new(2, 0., vec![1., 0.]),
+            &trajectory,
+            LeaderTrajectoryMode::Predefined,
+        );
+        let simulation = simulation.with_disturbances(vec![wind; 2]);
+        let data = simulation
+            .run(1., 0.1, vec![PerfectObserver {}; 2])
+            .into_data();
+        assert_eq!(data[1][0], 0.);
+        assert!((data[1][10] + 0.5).abs() < 1e-9);
+        assert!((data[0][10] - 2.).abs() < 1e-9);
+
+        let direction: Metres2D = random_direction(&mut SmallRng::from_entropy());
+        assert!((direction.length().abs() - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn recorded_channels() {
+        let trajectory = NaiveTrajectory::from_points(0.1, vec![(0., 0.), (1., 1.)]);
+        let recording = Recording {
+            true_position: true,
+            velocity: true,
+            sensed: true,
+            command: true,
+        };
+        let simulation = SimpleSimulation::new(
+            2,
+            0,
+            vec![PerfectSensor, PerfectSensor],
+            vec![PController::new(Default::default()); 2],
+            &SimpleFormation::new(2, 0., vec![1., 0.]),
+            &trajectory,
+            LeaderTrajectoryMode::Predefined,
+        );
+        let result = simulation
+            .with_recording(recording)
+            .run(1., 0.1, vec![PerfectObserver {}; 2]);
+        let channels = result.channels().to_vec();
+        let names: Vec<_> = channels.iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["true_pos", "v", "sensed", "command"]);
+        let (positions, velocities, sensed, commands) =
+            (&channels[0], &channels[1], &channels[2], &channels[3]);
+        assert_eq!(positions.kind, ChannelKind::Position);
+        assert_eq!(velocities.kind, ChannelKind::Rate);
+
+        // the leader is not controlled, so has no reading or command
+        assert!(sensed.data[0][0].is_nan() && commands.data[0][0].is_nan());
+        assert!((velocities.data[0][5] - 1.).abs() < 1e-9);
+        for step in 0..10 {
+            let offset = positions.data[0][step] - positions.data[1][step];
+            assert!((sensed.data[1][step] - offset).abs() < 1e-9);
+            assert!((velocities.data[1][step + 1] - commands.data[1][step]).abs() < 1e-9);
+        }
+        assert_eq!(result.into_data(), positions.data);
     }
 }
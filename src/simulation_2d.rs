@@ -401,9 +401,8 @@ impl VLPrescribedControl {
         &self,
         follower: &NonHolonomicDynamics,
         t: Seconds,
-        resolution: Seconds,
     ) -> (MetresPerSecond, RadiansPerSecond) {
-        let leader_dynamics = self.path.sample(t, resolution);
+        let leader_dynamics = self.path.sample(t);
         self.control.calculate_control(follower, &leader_dynamics)
     }
 }
@@ -436,7 +435,7 @@ impl VLShenPrescribedControl {
         t: Seconds,
         resolution: Seconds,
     ) -> (MetresPerSecond, RadiansPerSecond) {
-        let leader_dynamics = self.path.sample(t, resolution);
+        let leader_dynamics = self.path.sample(t);
         self.control
             .calculate_control(follower, &leader_dynamics, resolution)
     }
@@ -457,7 +456,8 @@ impl PrescribedControl {
         PrescribedControl { path, path_length }
     }
 
-    pub fn sample(&self, t: Seconds, resolution: Seconds) -> NonHolonomicDynamics {
+    /// The leader stops once it reaches the end of the path
+    pub fn sample(&self, t: Seconds) -> NonHolonomicDynamics {
         if t > self.path_length {
             let (position, heading) = self.path.endpoint();
             return NonHolonomicDynamics {
@@ -468,23 +468,10 @@ impl PrescribedControl {
             };
         }
 
-        let (position, heading) = self.path.sample(t).expect("Invalid t parameter given");
-        let (next_t, next_position, next_heading) = {
-            let next_t = t + resolution;
-            if next_t > self.path_length {
-                let (pos, rot) = self.path.endpoint();
-                (self.path_length, pos, rot)
-            } else {
-                let (pos, rot) = self
-                    .path
-                    .sample(next_t)
-                    .expect("Invalid t parameter in next point sampling");
-                (next_t, pos, rot)
-            }
-        };
-        let delta_t = next_t - t;
-        let speed = (next_position - position).length() / delta_t;
-        let angular_velocity = (next_heading - heading) / delta_t;
+        let ((position, heading), (speed, angular_velocity)) = self
+            .path
+            .sample_dynamics(t)
+            .expect("Invalid t parameter given");
 
         NonHolonomicDynamics {
             position,
@@ -573,7 +560,7 @@ impl<'a> System<'a> for ApplyNonHolonomicDynamics {
 
         // leaders
         for (dynamic, control) in (&mut dynamics, &prescribed).join() {
-            let new_data = control.sample(time.sim_time(), time.sim_delta());
+            let new_data = control.sample(time.sim_time());
             *dynamic = new_data;
         }
     }
@@ -637,11 +624,8 @@ impl<'a> System<'a> for ApplyControl {
         }
 
         for (follower_entity, follower, control) in (&*entities, &dynamics, &vlp).join() {
-            let new_dynamics = DynamicsChange::new(control.calculate_control(
-                follower,
-                time.sim_time(),
-                time.sim_delta(),
-            ));
+            let new_dynamics =
+                DynamicsChange::new(control.calculate_control(follower, time.sim_time()));
             self.new_dynamics.add(follower_entity, new_dynamics);
         }
 
@@ -1018,38 +1002,58 @@ mod tests {
         let left = OrientedPosition2D::new(-2., -2., PI / 2.);
         let right = OrientedPosition2D::new(2., -2., PI / 2.);
         let back = OrientedPosition2D::new(0., -4., PI / 2.);
-        let multi = MultiDubinsPath::generate(1., 2., 15., &mut rng, origin, 10.)
+        let multi = MultiDubinsPath::generate(1., 2., 15., &mut rng, origin, 10., &[])
             .expect("could not generate");
         let specs = vec![
             NonHolonomicRobotSpec {
                 id: "leader".to_string(),
-                control: DesaiControl::Prescribed { path: multi },
+                control: RobotControl::Desai(DesaiControl::Prescribed { path: multi }),
                 initial_configuration: origin,
             },
             NonHolonomicRobotSpec {
                 id: "left".to_string(),
-                control: DesaiControl::LPsi {
+                control: RobotControl::Desai(DesaiControl::LPsi {
                     leader: "leader".to_string(),
-                },
+                }),
                 initial_configuration: left,
             },
             NonHolonomicRobotSpec {
                 id: "right".to_string(),
-                control: DesaiControl::LPsi {
+                control: RobotControl::Desai(DesaiControl::LPsi {
                     leader: "leader".to_string(),
-                },
+                }),
                 initial_configuration: right,
             },
             NonHolonomicRobotSpec {
                 id: "back".to_string(),
-                control: DesaiControl::LL {
+                control: RobotControl::Desai(DesaiControl::LL {
                     leaders: ("left".to_string(), "right".to_string()),
-                },
+                }),
                 initial_configuration: back,
             },
         ];
 
-        let (results, _) = do_desai_simulation(specs, 1. / 64., 1. / 8., None);
+        let (results, _) = do_desai_simulation(specs, 1. / 64., 1. / 8., None, false, 15.);
         println!("Simulation results: {:?}", results);
     }
+
+    #[test]
+    fn prescribed_control_near_end() {
+        let mut rng = thread_rng();
+        let origin = OrientedPosition2D::new(0., 0., PI / 2.);
+        let multi = MultiDubinsPath::generate(1., 2., 15., &mut rng, origin, 10., &[])
+            .expect("could not generate");
+        let control = PrescribedControl::new(multi);
+        let end = control.path_length;
+
+        // still driving right up to the end of the path
+        let dynamics = control.sample(end - 1. / 64.);
+        assert_eq!(dynamics.speed, 2.);
+        assert!(dynamics.angular_velocity.abs() <= 2. + 1e-9);
+
+        // and stopped afterwards
+        let dynamics = control.sample(end + 1. / 64.);
+        assert_eq!(dynamics.speed, 0.);
+        assert_eq!(dynamics.angular_velocity, 0.);
+    }
 }
@@ -1,8 +1,8 @@
+use augmentation::Transform;
 use base::*;
 use csv;
-use dubins::MultiDubinsPath;
+use dubins::{DubinsPathType, MultiDubinsPath, Waypoint};
 use failure::Error;
-use num::Zero;
 use rand::distributions::{Distribution, Normal, Range, StandardNormal, Uniform};
 use rand::rngs::SmallRng;
 use rand::thread_rng;
@@ -19,15 +19,14 @@ use std;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
-use std::ops::DerefMut;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tf_record;
 use time;
 use trajectory;
 
 type Result<R> = ::std::result::Result<R, Error>;
 type Params = HashMap<String, ConstantParam>;
-type TrajectorySets<S> = Vec<(Params, Vec<trajectory::NaiveTrajectory<S>>)>;
+type TrajectorySets<T> = Vec<(Params, Vec<T>)>;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct ScenarioSpec {
@@ -47,6 +46,22 @@ pub struct ScenarioSpec {
     pub output_csv: bool,
     #[serde(default)]
     pub override_trajectory: Option<OverrideTrajSpec>,
+    /// Record the heading of every robot, only for 2 dimensions where it is done by default
+    #[serde(default)]
+    pub record_heading: Option<bool>,
+    #[serde(default)]
+    pub augmentation: Option<AugmentationSpec>,
+    /// Which robot each follower follows, the leader by default
+    #[serde(default)]
+    pub topology: simulation::Topology,
+    /// Filters the sensor readings each controller acts on
+    #[serde(default)]
+    pub estimator: Option<EstimatorSpec>,
+    #[serde(default)]
+    pub disturbances: Option<DisturbanceSpec>,
+    /// What else to record for each robot besides its observed position
+    #[serde(default)]
+    pub record: simulation::Recording,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -54,22 +69,320 @@ pub struct ScenarioSpec {
 pub enum OverrideTrajSpec {
     TwoD(Vec<(Seconds, Metres2D)>),
     OneD(Vec<(Seconds, Metres)>),
+    ThreeD(Vec<(Seconds, Metres3D)>),
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "type")]
 pub enum ObserverSpec {
-    Simple { error: f64 },
+    Simple {
+        error: f64,
+    },
     Perfect {},
+    /// Constant offset on each axis
+    Bias {
+        offset: Vec<f64>,
+    },
+    /// Offset by a random walk, with steps of standard deviation `step_sd` on each axis
+    Drift {
+        step_sd: f64,
+    },
+    /// Gross errors of standard deviation `error` in a fraction `probability` of observations
+    Outliers {
+        probability: f64,
+        error: f64,
+    },
+    /// Missed observations, which are recorded as NaN
+    Dropout {
+        probability: f64,
+    },
+    /// Applies each observer in turn
+    Combined {
+        observers: Vec<ObserverSpec>,
+    },
+}
+
+impl Default for ObserverSpec {
+    fn default() -> Self {
+        ObserverSpec::Perfect {}
+    }
 }
 
 impl ObserverSpec {
-    fn get_observer(&self) -> simulation::SimpleObserver {
+    fn name(&self) -> &'static str {
+        match *self {
+            ObserverSpec::Simple { .. } => "simple",
+            ObserverSpec::Perfect {} => "perfect",
+            ObserverSpec::Bias { .. } => "bias",
+            ObserverSpec::Drift { .. } => "drift",
+            ObserverSpec::Outliers { .. } => "outliers",
+            ObserverSpec::Dropout { .. } => "dropout",
+            ObserverSpec::Combined { .. } => "combined",
+        }
+    }
+
+    fn validate(&self, dimensions: usize) -> Result<()> {
+        let is_probability = |p: f64| (0. ..=1.).contains(&p);
+        match *self {
+            ObserverSpec::Simple { error } => ensure!(error >= 0., "error must not be negative"),
+            ObserverSpec::Perfect {} => {}
+            ObserverSpec::Bias { ref offset } => ensure!(
+                offset.len() == dimensions,
+                "bias must have one offset per dimension"
+            ),
+            ObserverSpec::Drift { step_sd } => {
+                ensure!(step_sd >= 0., "drift step must not be negative")
+            }
+            ObserverSpec::Outliers { probability, error } => ensure!(
+                is_probability(probability) && error >= 0.,
+                "outlier probability must be between 0 and 1, and error not negative"
+            ),
+            ObserverSpec::Dropout { probability } => ensure!(
+                is_probability(probability),
+                "dropout probability must be between 0 and 1"
+            ),
+            ObserverSpec::Combined { ref observers } => {
+                for observer in observers.iter() {
+                    observer.validate(dimensions)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether some observations may be missed
+    fn has_dropout(&self) -> bool {
+        match *self {
+            ObserverSpec::Dropout { .. } => true,
+            ObserverSpec::Combined { ref observers } => observers.iter().any(|o| o.has_dropout()),
+            _ => false,
+        }
+    }
+
+    fn get_observer<S: Vector + 'static>(&self) -> Box<dyn simulation::Observer<S>>
+    where
+        simulation::SimpleObserver: simulation::Observer<S>,
+    {
+        match *self {
+            ObserverSpec::Perfect {} => Box::new(simulation::SimpleObserver::new(0.)),
+            ObserverSpec::Simple { error } => Box::new(simulation::SimpleObserver::new(error)),
+            ObserverSpec::Bias { ref offset } => {
+                Box::new(simulation::BiasObserver::new(S::from_repr(offset)))
+            }
+            ObserverSpec::Drift { step_sd } => {
+                Box::new(simulation::DriftObserver::<S>::new(step_sd))
+            }
+            ObserverSpec::Outliers { probability, error } => {
+                Box::new(simulation::OutlierObserver::new(probability, error))
+            }
+            ObserverSpec::Dropout { probability } => {
+                Box::new(simulation::DropoutObserver::new(probability))
+            }
+            ObserverSpec::Combined { ref observers } => {
+                let observers = observers.iter().map(|o| o.get_observer()).collect();
+                Box::new(simulation::CombinedObserver::new(observers))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum EstimatorSpec {
+    Kalman(simulation::KalmanFilterParams),
+}
+
+impl EstimatorSpec {
+    fn validate(&self) -> Result<()> {
         match *self {
-            ObserverSpec::Perfect {} => simulation::SimpleObserver::new(0.),
-            ObserverSpec::Simple { error } => simulation::SimpleObserver::new(error),
+            EstimatorSpec::Kalman(ref params) => {
+                params.validate().map_err(|err| format_err!("{}", err))
+            }
+        }
+    }
+}
+
+/// Disturbances pushing robots off course, whose magnitudes are sampled for each robot in each episode
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DisturbanceSpec {
+    /// Speed of a constant wind at each robot. The wind blows in the same random direction for every robot
+    #[serde(default)]
+    pub wind: Option<RandomParamSpec>,
+    /// Standard deviation of gaussian noise added to the velocity along each axis at every step
+    #[serde(default)]
+    pub process_noise: Option<RandomParamSpec>,
+    /// Mean number of pushes per second
+    #[serde(default)]
+    pub impulse_rate: f64,
+    /// Distance of each push
+    #[serde(default)]
+    pub impulse: Option<RandomParamSpec>,
+}
+
+/// Samples the disturbances of each robot for each episode
+struct DisturbanceGenerator {
+    wind: GenericFloatParam,
+    process_noise: GenericFloatParam,
+    impulse_rate: f64,
+    impulse: GenericFloatParam,
+}
+
+impl DisturbanceGenerator {
+    fn new(spec: &DisturbanceSpec) -> Result<Self> {
+        ensure!(spec.impulse_rate >= 0., "impulse rate must not be negative");
+        // magnitudes which are not given are 0
+        let magnitude = |name: &str, magnitude: &Option<RandomParamSpec>| {
+            let mut values = HashMap::new();
+            values.insert(
+                name.to_string(),
+                magnitude
+                    .clone()
+                    .unwrap_or(RandomParamSpec::Constant { value: 0. }),
+            );
+            GenericFloatParam::from_param(&ParamsSpec::Random { values }, name)
+        };
+        Ok(DisturbanceGenerator {
+            wind: magnitude("wind", &spec.wind)?,
+            process_noise: magnitude("process_noise", &spec.process_noise)?,
+            impulse_rate: spec.impulse_rate,
+            impulse: magnitude("impulse", &spec.impulse)?,
+        })
+    }
+
+    /// The wind direction is drawn once, so every robot is blown the same way
+    fn generate<S: Vector, R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        num_robots: usize,
+    ) -> Vec<simulation::Disturbance<S>> {
+        let wind_direction = simulation::random_direction(rng);
+        (0..num_robots)
+            .map(|_| simulation::Disturbance {
+                wind: self.wind.sample(rng).max(0.),
+                wind_direction,
+                process_noise: self.process_noise.sample(rng).max(0.),
+                impulse_rate: self.impulse_rate,
+                impulse: self.impulse.sample(rng).max(0.),
+            })
+            .collect()
+    }
+
+    /// Records the disturbance magnitudes of each robot, numbered as in the transformed episode,
+    /// and the transformed wind direction as `wind_direction_{axis}`
+    fn record<S: Vector>(
+        disturbances: &[simulation::Disturbance<S>],
+        transform: &Transform,
+        params: &mut Params,
+    ) {
+        if let Some(disturbance) = disturbances.first() {
+            // the wind is a velocity, so turns around when time is reversed
+            let direction = transform.vector(disturbance.wind_direction, true).repr();
+            for (axis, value) in ["x", "y", "z"].iter().zip(direction) {
+                params.insert(
+                    format!("wind_direction_{}", axis),
+                    ConstantParam::Float(value),
+                );
+            }
+        }
+        for (robot, disturbance) in disturbances.iter().enumerate() {
+            let prefix = format!("r{}_", transform.robot_index(robot));
+            let magnitudes = [
+                ("wind", disturbance.wind),
+                ("process_noise", disturbance.process_noise),
+                ("impulse", disturbance.impulse),
+            ];
+            for &(name, magnitude) in magnitudes.iter() {
+                params.insert(prefix.clone() + name, ConstantParam::Float(magnitude));
+            }
+        }
+    }
+}
+
+/// Augments a dataset as it is written: every episode is written as simulated,
+/// followed by `copies` copies under randomly drawn transforms
+#[derive(Debug, Clone, Deserialize)]
+pub struct AugmentationSpec {
+    pub copies: usize,
+    /// Rotate by a uniformly random angle about the origin, needs at least 2 dimensions
+    #[serde(default)]
+    pub rotate: bool,
+    /// Reflect with probability 1/2
+    #[serde(default)]
+    pub reflect: bool,
+    /// Translate by up to this distance along each axis
+    #[serde(default)]
+    pub translate: Metres,
+    /// Reverse time with probability 1/2, not for Dubins robots which only drive forwards.
+    /// In reversed copies the followers react before the leader moves, so the recorded `leader` is the robot
+    /// which drove the reference trajectory but no longer the one the others respond to.
+    /// Whether each copy is reversed is recorded as `time_reversed` next to `leader`.
+    #[serde(default)]
+    pub time_reverse: bool,
+    /// Shuffle the order of the robots
+    #[serde(default)]
+    pub permute_robots: bool,
+}
+
+impl AugmentationSpec {
+    fn validate(&self, dimensions: usize, time_reversible: bool) -> Result<()> {
+        ensure!(
+            !self.rotate || dimensions >= 2,
+            "rotation needs at least 2 dimensions"
+        );
+        ensure!(
+            self.translate >= 0. && self.translate.is_finite(),
+            "translation distance must be finite and not negative"
+        );
+        ensure!(
+            !self.time_reverse || time_reversible,
+            "time reversal is not physically meaningful for these robots"
+        );
+        Ok(())
+    }
+
+    fn transform<R: Rng>(&self, rng: &mut R, num_robots: usize, dimensions: usize) -> Transform {
+        let mut transform = Transform::identity(num_robots, dimensions);
+        transform.reflect = self.reflect && rng.gen();
+        if self.rotate {
+            transform.rotation = rng.gen_range(-std::f64::consts::PI, std::f64::consts::PI);
+        }
+        if self.translate > 0. {
+            transform.translation = (0..dimensions)
+                .map(|_| rng.gen_range(-self.translate, self.translate))
+                .collect();
+        }
+        transform.time_reversed = self.time_reverse && rng.gen();
+        if self.permute_robots {
+            rng.shuffle(&mut transform.permutation);
+        }
+        transform
+    }
+}
+
+/// The transforms to write an episode with, starting with the identity
+fn augmentation_transforms<R: Rng>(
+    spec: &Option<AugmentationSpec>,
+    rng: &mut R,
+    num_robots: usize,
+    dimensions: usize,
+) -> Vec<Transform> {
+    let mut transforms = vec![Transform::identity(num_robots, dimensions)];
+    if let Some(ref spec) = *spec {
+        for _ in 0..spec.copies {
+            transforms.push(spec.transform(rng, num_robots, dimensions));
         }
     }
+    transforms
+}
+
+/// Adds the transform that produced a record to its parameters, if the dataset is augmented
+fn record_transform(params: &mut Params, spec: &Option<AugmentationSpec>, transform: &Transform) {
+    if spec.is_some() {
+        params.insert(
+            "augmentation".to_string(),
+            ConstantParam::Text(transform.to_string()),
+        );
+    }
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -77,16 +390,47 @@ pub struct RobotSpec {
     pub max_speed: f64,
     pub controller: ControllerSpec,
     pub num_robots: usize,
+    #[serde(default)]
+    pub sensor: SensorSpec,
+    /// Controllers, sensors and observers of individual robots, replacing the ones above
+    #[serde(default)]
+    pub overrides: Vec<RobotOverrideSpec>,
+    /// Distributions to sample the controller gains of each robot from for each episode,
+    /// for robots without an overridden controller
+    #[serde(default)]
+    pub gains: HashMap<String, RandomParamSpec>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RobotOverrideSpec {
+    pub robot: usize,
+    #[serde(default)]
+    pub controller: Option<ControllerSpec>,
+    #[serde(default)]
+    pub sensor: Option<SensorSpec>,
+    /// How this robot is observed, replacing the scenario's observer
+    #[serde(default)]
+    pub observer: Option<ObserverSpec>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum ControllerSpec {
+    P {
+        p_gain: f64,
+    },
     PID {
         p_gain: f64,
         i_gain: f64,
         d_gain: f64,
     },
+    /// Gains for each axis, saturating the velocity vector at the maximum speed. Only for 2 dimensions
+    PID2D {
+        x: simulation::PIDGains,
+        y: simulation::PIDGains,
+        #[serde(default)]
+        anti_windup: simulation::AntiWindup,
+    },
 }
 
 impl Default for ControllerSpec {
@@ -100,34 +444,284 @@ impl Default for ControllerSpec {
 }
 
 impl ControllerSpec {
-    pub fn get_1d_controller(&self, max_speed: f64) -> simulation::PIDController {
-        let ControllerSpec::PID {
-            p_gain,
-            i_gain,
-            d_gain,
-        } = *self;
-        let params = simulation::PIDControllerParams {
-            p_gain,
-            d_gain,
-            i_gain,
-            vel_limits: (-max_speed, max_speed),
+    fn name(&self) -> &'static str {
+        match *self {
+            ControllerSpec::P { .. } => "P",
+            ControllerSpec::PID { .. } => "PID",
+            ControllerSpec::PID2D { .. } => "PID2D",
+        }
+    }
+
+    fn validate(&self, dimensions: usize) -> Result<()> {
+        if let ControllerSpec::PID2D { anti_windup, .. } = *self {
+            ensure!(
+                dimensions == 2,
+                "PID2D controllers are only for 2 dimensions"
+            );
+            if let simulation::AntiWindup::BackCalculation { gain } = anti_windup {
+                ensure!(gain >= 0., "back calculation gain must not be negative");
+            }
+        }
+        Ok(())
+    }
+
+    fn gains(&self) -> Vec<(&'static str, f64)> {
+        match *self {
+            ControllerSpec::P { p_gain } => vec![("p_gain", p_gain)],
+            ControllerSpec::PID {
+                p_gain,
+                i_gain,
+                d_gain,
+            } => vec![("p_gain", p_gain), ("i_gain", i_gain), ("d_gain", d_gain)],
+            ControllerSpec::PID2D { x, y, .. } => vec![
+                ("x_p_gain", x.p_gain),
+                ("x_i_gain", x.i_gain),
+                ("x_d_gain", x.d_gain),
+                ("y_p_gain", y.p_gain),
+                ("y_i_gain", y.i_gain),
+                ("y_d_gain", y.d_gain),
+            ],
+        }
+    }
+
+    fn set_gain(&mut self, name: &str, value: f64) -> Result<()> {
+        let gain = match (self, name) {
+            (&mut ControllerSpec::P { ref mut p_gain }, "p_gain") => p_gain,
+            (&mut ControllerSpec::PID { ref mut p_gain, .. }, "p_gain") => p_gain,
+            (&mut ControllerSpec::PID { ref mut i_gain, .. }, "i_gain") => i_gain,
+            (&mut ControllerSpec::PID { ref mut d_gain, .. }, "d_gain") => d_gain,
+            (&mut ControllerSpec::PID2D { ref mut x, .. }, "x_p_gain") => &mut x.p_gain,
+            (&mut ControllerSpec::PID2D { ref mut x, .. }, "x_i_gain") => &mut x.i_gain,
+            (&mut ControllerSpec::PID2D { ref mut x, .. }, "x_d_gain") => &mut x.d_gain,
+            (&mut ControllerSpec::PID2D { ref mut y, .. }, "y_p_gain") => &mut y.p_gain,
+            (&mut ControllerSpec::PID2D { ref mut y, .. }, "y_i_gain") => &mut y.i_gain,
+            (&mut ControllerSpec::PID2D { ref mut y, .. }, "y_d_gain") => &mut y.d_gain,
+            (controller, name) => bail!("{} controllers have no gain {}", controller.name(), name),
         };
-        simulation::PIDController::new(params)
+        *gain = value;
+        Ok(())
     }
 
-    pub fn get_2d_controller(&self, max_speed: f64) -> simulation::UniformPIDController2D {
-        let ControllerSpec::PID {
-            p_gain,
-            i_gain,
-            d_gain,
-        } = *self;
-        let params = simulation::PIDControllerParams {
+    /// Proportional controllers are PID controllers without the other terms
+    fn pid_params(&self, max_speed: f64) -> Result<simulation::PIDControllerParams> {
+        let (p_gain, i_gain, d_gain) = match *self {
+            ControllerSpec::P { p_gain } => (p_gain, 0., 0.),
+            ControllerSpec::PID {
+                p_gain,
+                i_gain,
+                d_gain,
+            } => (p_gain, i_gain, d_gain),
+            ControllerSpec::PID2D { .. } => bail!("PID2D controllers are only for 2 dimensions"),
+        };
+        Ok(simulation::PIDControllerParams {
             p_gain,
             d_gain,
             i_gain,
             vel_limits: (-max_speed, max_speed),
+        })
+    }
+
+    pub fn get_1d_controller(&self, max_speed: f64) -> Result<simulation::BoxedController<f64>> {
+        Ok(match *self {
+            ControllerSpec::P { p_gain } => {
+                let params = simulation::PControllerParams {
+                    p_gain,
+                    vel_limits: (-max_speed, max_speed),
+                };
+                Box::new(simulation::PController::new(params))
+            }
+            _ => Box::new(simulation::PIDController::new(self.pid_params(max_speed)?)),
+        })
+    }
+
+    pub fn get_2d_controller(
+        &self,
+        max_speed: f64,
+    ) -> Result<simulation::BoxedController<Metres2D>> {
+        Ok(match *self {
+            ControllerSpec::PID2D { x, y, anti_windup } => {
+                let params = simulation::PIDController2DParams {
+                    x,
+                    y,
+                    max_speed,
+                    anti_windup,
+                };
+                Box::new(simulation::PIDController2D::new(params))
+            }
+            _ => Box::new(simulation::UniformPIDController2D::new(
+                self.pid_params(max_speed)?,
+            )),
+        })
+    }
+
+    pub fn get_3d_controller(
+        &self,
+        max_speed: f64,
+    ) -> Result<simulation::BoxedController<Metres3D>> {
+        Ok(Box::new(simulation::UniformPIDController3D::new(
+            self.pid_params(max_speed)?,
+        )))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum SensorSpec {
+    /// Sharp IR range noise, with encoder noise on the bearing in 2 and 3 dimensions
+    SharpIr {},
+    Perfect {},
+    /// Range noise, limits and dropouts as configured
+    Range(simulation::RangeSensorParams),
+}
+
+impl Default for SensorSpec {
+    fn default() -> Self {
+        SensorSpec::SharpIr {}
+    }
+}
+
+impl SensorSpec {
+    fn name(&self) -> &'static str {
+        match *self {
+            SensorSpec::SharpIr {} => "sharp_ir",
+            SensorSpec::Perfect {} => "perfect",
+            SensorSpec::Range(_) => "range",
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let SensorSpec::Range(ref params) = *self {
+            params.validate().map_err(|err| format_err!("{}", err))?;
+        }
+        Ok(())
+    }
+
+    fn get_1d_sensor(&self) -> simulation::BoxedDistanceSensor<Metres> {
+        match *self {
+            SensorSpec::SharpIr {} => Box::new(simulation::SharpIrSensor::new()),
+            SensorSpec::Perfect {} => Box::new(simulation::PerfectSensor),
+            SensorSpec::Range(ref params) => Box::new(simulation::RangeSensor::new(params.clone())),
+        }
+    }
+
+    fn get_2d_sensor(&self) -> simulation::BoxedDistanceSensor<Metres2D> {
+        match *self {
+            SensorSpec::SharpIr {} => Box::new(simulation::CombinedIrEncoderSensor::new()),
+            SensorSpec::Perfect {} => Box::new(simulation::PerfectSensor),
+            SensorSpec::Range(ref params) => Box::new(simulation::RangeSensor::new(params.clone())),
+        }
+    }
+
+    fn get_3d_sensor(&self) -> simulation::BoxedDistanceSensor<Metres3D> {
+        match *self {
+            SensorSpec::SharpIr {} => Box::new(simulation::CombinedIrEncoderSensor::new()),
+            SensorSpec::Perfect {} => Box::new(simulation::PerfectSensor),
+            SensorSpec::Range(ref params) => Box::new(simulation::RangeSensor::new(params.clone())),
+        }
+    }
+}
+
+/// Chooses the controller and sensor of every robot for each episode
+struct RobotGenerator {
+    /// Whether every robot always has the same controller and sensor
+    uniform: bool,
+    robots: Vec<(ControllerSpec, SensorSpec)>,
+    /// Robots whose gains are sampled
+    sampled: Vec<bool>,
+    gains: Vec<(String, GenericFloatParam)>,
+    /// The observer of each robot, a new one of which is made for every simulation
+    observers: Vec<ObserverSpec>,
+}
+
+impl RobotGenerator {
+    fn new(spec: &RobotSpec, observer: &ObserverSpec, dimensions: usize) -> Result<Self> {
+        let mut robots = vec![(spec.controller.clone(), spec.sensor.clone()); spec.num_robots];
+        let mut sampled = vec![true; spec.num_robots];
+        let mut observers = vec![observer.clone(); spec.num_robots];
+        for o in spec.overrides.iter() {
+            ensure!(
+                o.robot < spec.num_robots,
+                "override for robot {} but there are only {} robots",
+                o.robot,
+                spec.num_robots
+            );
+            if let Some(ref controller) = o.controller {
+                robots[o.robot].0 = controller.clone();
+                sampled[o.robot] = false;
+            }
+            if let Some(ref sensor) = o.sensor {
+                robots[o.robot].1 = sensor.clone();
+            }
+            if let Some(ref observer) = o.observer {
+                observers[o.robot] = observer.clone();
+            }
+        }
+        for &(ref controller, ref sensor) in robots.iter() {
+            controller.validate(dimensions)?;
+            sensor.validate()?;
+        }
+        for observer in observers.iter() {
+            observer.validate(dimensions)?;
+        }
+        let params = ParamsSpec::Random {
+            values: spec.gains.clone(),
         };
-        simulation::UniformPIDController2D::new(params)
+        let mut gains = Vec::with_capacity(spec.gains.len());
+        for name in spec.gains.keys() {
+            spec.controller.clone().set_gain(name, 0.)?;
+            gains.push((name.clone(), GenericFloatParam::from_param(&params, name)?));
+        }
+        Ok(RobotGenerator {
+            uniform: spec.overrides.is_empty() && gains.is_empty(),
+            robots,
+            sampled,
+            gains,
+            observers,
+        })
+    }
+
+    /// Whether some robot may not be observed at some steps
+    fn has_dropout(&self) -> bool {
+        self.observers.iter().any(|o| o.has_dropout())
+    }
+
+    fn generate<R: Rng + ?Sized>(&self, rng: &mut R) -> Vec<(ControllerSpec, SensorSpec)> {
+        let mut robots = self.robots.clone();
+        for (robot, &sampled) in robots.iter_mut().zip(self.sampled.iter()) {
+            if sampled {
+                for &(ref name, ref dist) in self.gains.iter() {
+                    robot.0.set_gain(name, dist.sample(rng)).unwrap();
+                }
+            }
+        }
+        robots
+    }
+
+    /// Records the controller, sensor and observer of each robot, numbered as in the transformed episode
+    fn record(
+        &self,
+        robots: &[(ControllerSpec, SensorSpec)],
+        transform: &Transform,
+        params: &mut Params,
+    ) {
+        for (robot, &(ref controller, ref sensor)) in robots.iter().enumerate() {
+            let prefix = format!("r{}_", transform.robot_index(robot));
+            params.insert(
+                prefix.clone() + "controller",
+                ConstantParam::Text(controller.name().to_string()),
+            );
+            for (gain, value) in controller.gains() {
+                params.insert(prefix.clone() + gain, ConstantParam::Float(value));
+            }
+            params.insert(
+                prefix.clone() + "sensor",
+                ConstantParam::Text(sensor.name().to_string()),
+            );
+            params.insert(
+                prefix + "observer",
+                ConstantParam::Text(self.observers[robot].name().to_string()),
+            );
+        }
     }
 }
 
@@ -148,6 +742,14 @@ pub enum ParamsSpec {
     },
 }
 
+impl Default for ParamsSpec {
+    fn default() -> Self {
+        ParamsSpec::Constant {
+            values: HashMap::new(),
+        }
+    }
+}
+
 impl ParamsSpec {
     fn specialise<R: Rng + ?Sized>(&self, rng: &mut R) -> Params {
         match *self {
@@ -193,7 +795,7 @@ impl ParamsSpec {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case", tag = "dist")]
 pub enum RandomParamSpec {
     Uniform { range: (f64, f64) },
@@ -206,6 +808,7 @@ pub enum RandomParamSpec {
 pub enum ConstantParam {
     Float(f64),
     Int(i64),
+    Text(String),
 }
 
 impl ConstantParam {
@@ -223,24 +826,417 @@ pub struct ReferenceTrajectorySpec {
     pub num_sets: usize,
     pub num_per_set: usize,
     pub generator: String,
+    #[serde(default)]
     pub params: ParamsSpec,
+    /// Where to read trajectories from when using the `recorded` generator
+    #[serde(default)]
+    pub recorded: Option<RecordedTrajectorySpec>,
+    #[serde(default)]
+    pub limits: Option<KinematicLimitsSpec>,
 }
 
-#[derive(Debug, Clone, Default, Serialize)]
-pub struct DatasetDescription {
-    pub num_robots: usize,
-    pub dimensions: usize,
-    pub points_per_trajectory: usize,
-    pub resolution: f64,
-    pub features: Params,
-    pub controller: ControllerSpec,
-    pub files: Vec<DataFileDescription>,
-}
+impl ReferenceTrajectorySpec {
+    /// Generates the sets of reference trajectories, using `G` unless they are recorded ones.
+    /// If `override_points` is given every trajectory is made from them instead.
+    fn trajectory_sets<S, T, G, R>(
+        &self,
+        length: Seconds,
+        resolution: Seconds,
+        max_speed: MetresPerSecond,
+        override_points: Option<&Vec<(Seconds, S)>>,
+        rng: &mut R,
+    ) -> Result<TrajectorySets<T>>
+    where
+        S: RecordedVector,
+        T: trajectory::UniformResolutionTrajectory<S> + From<trajectory::NaiveTrajectory<S>>,
+        G: TrajectoryGenerator<T>,
+        R: Rng + ?Sized,
+    {
+        if self.generator == "recorded" {
+            let recorded = self.recorded.as_ref().ok_or(format_err!(
+                "the recorded generator needs a recorded trajectory directory"
+            ))?;
+            let trajectory_sets =
+                recorded.trajectory_sets(length, resolution, self.num_sets, self.num_per_set)?;
+            let mut checked_sets: TrajectorySets<T> = Vec::with_capacity(self.num_sets);
+            for (mut params, set) in trajectory_sets {
+                let mut checker = FeasibilityChecker::new(self.limits.as_ref(), max_speed);
+                let mut checked_set = Vec::with_capacity(set.len());
+                for trajectory in set {
+                    // recorded trajectories cannot be regenerated, so are never rejected without an error
+                    checked_set.extend(checker.check(T::from(trajectory), false)?);
+                }
+                checker.record(&mut params);
+                checked_sets.push((params, checked_set));
+            }
+            return Ok(checked_sets);
+        }
 
-#[derive(Debug, Clone, Default, Serialize)]
-pub struct DataFileDescription {
-    pub file: String,
-    pub num_trajectories: usize,
+        let mut trajectory_sets: TrajectorySets<T> = Vec::with_capacity(self.num_sets);
+        for _ in 0..self.num_sets {
+            let mut params = self.params.specialise(rng);
+            let generator = G::from_params(&self.generator, &params)?;
+            generator.record_params(&mut params);
+            let mut checker = FeasibilityChecker::new(self.limits.as_ref(), max_speed);
+            let mut set: Vec<T> = Vec::with_capacity(self.num_per_set);
+            for _ in 0..self.num_per_set {
+                let mut attempts = 1;
+                loop {
+                    let trajectory = match override_points {
+                        Some(points) => T::from(trajectory::NaiveTrajectory::from_points(
+                            resolution,
+                            points.clone(),
+                        )),
+                        None => generator.generate(length, resolution, max_speed),
+                    };
+                    let can_retry =
+                        override_points.is_none() && attempts < MAX_FEASIBILITY_ATTEMPTS;
+                    if let Some(trajectory) = checker.check(trajectory, can_retry)? {
+                        set.push(trajectory);
+                        break;
+                    }
+                    attempts += 1;
+                }
+            }
+            checker.record(&mut params);
+            trajectory_sets.push((params, set));
+        }
+        Ok(trajectory_sets)
+    }
+}
+
+/// How many times an infeasible trajectory is regenerated before giving up
+const MAX_FEASIBILITY_ATTEMPTS: usize = 100;
+
+/// Kinematic limits on the reference trajectories, which give each trajectory set a feasibility report.
+/// The report records the peak speed, acceleration, jerk and turn rate of the set, and how many of its
+/// trajectories exceed the robots' maximum speed or the limits.
+#[derive(Debug, Clone, Deserialize)]
+pub struct KinematicLimitsSpec {
+    #[serde(default)]
+    pub max_accel: Option<f64>,
+    #[serde(default)]
+    pub max_jerk: Option<f64>,
+    /// Only applies in 2D, in radians per second
+    #[serde(default)]
+    pub max_turn_rate: Option<f64>,
+    /// Filter the trajectories so that they respect the limits
+    #[serde(default)]
+    pub enforce: bool,
+    /// Regenerate trajectories which are still infeasible, failing if they are recorded or overridden
+    #[serde(default)]
+    pub reject_infeasible: bool,
+}
+
+impl KinematicLimitsSpec {
+    fn limits(&self) -> trajectory::KinematicLimits {
+        trajectory::KinematicLimits {
+            max_accel: self.max_accel,
+            max_jerk: self.max_jerk,
+            max_turn_rate: self.max_turn_rate,
+        }
+    }
+}
+
+/// Applies the kinematic limits to the trajectories of a set, collecting their feasibility report
+struct FeasibilityChecker<'a> {
+    spec: Option<&'a KinematicLimitsSpec>,
+    max_speed: MetresPerSecond,
+    peak: trajectory::FeasibilityReport,
+    num_infeasible: usize,
+}
+
+impl<'a> FeasibilityChecker<'a> {
+    fn new(spec: Option<&'a KinematicLimitsSpec>, max_speed: MetresPerSecond) -> Self {
+        FeasibilityChecker {
+            spec,
+            max_speed,
+            peak: Default::default(),
+            num_infeasible: 0,
+        }
+    }
+
+    /// Filters the trajectory if the limits are enforced. Returns None if it is infeasible and should be
+    /// regenerated, which is only allowed if `can_retry`.
+    fn check<S, T>(&mut self, trajectory: T, can_retry: bool) -> Result<Option<T>>
+    where
+        S: Vector,
+        T: trajectory::UniformResolutionTrajectory<S> + From<trajectory::NaiveTrajectory<S>>,
+    {
+        let spec = match self.spec {
+            Some(spec) => spec,
+            None => return Ok(Some(trajectory)),
+        };
+        let limits = spec.limits();
+        let mut naive = trajectory::NaiveTrajectory::from_trajectory(&trajectory);
+        let trajectory = if spec.enforce {
+            naive = trajectory::limit_kinematics(&naive, self.max_speed, &limits);
+            T::from(naive.clone())
+        } else {
+            trajectory
+        };
+        let report = trajectory::FeasibilityReport::new(&naive);
+        if !report.is_feasible(self.max_speed, &limits) {
+            if spec.reject_infeasible {
+                ensure!(
+                    can_retry,
+                    "could not find a feasible reference trajectory: {:?}",
+                    report
+                );
+                return Ok(None);
+            }
+            self.num_infeasible += 1;
+        }
+        self.peak = trajectory::FeasibilityReport {
+            peak_speed: self.peak.peak_speed.max(report.peak_speed),
+            peak_accel: self.peak.peak_accel.max(report.peak_accel),
+            peak_jerk: self.peak.peak_jerk.max(report.peak_jerk),
+            peak_turn_rate: self.peak.peak_turn_rate.max(report.peak_turn_rate),
+        };
+        Ok(Some(trajectory))
+    }
+
+    fn record(&self, params: &mut Params) {
+        if self.spec.is_none() {
+            return;
+        }
+        if self.num_infeasible > 0 {
+            warn!(
+                "{} reference trajectories in a set are kinematically infeasible",
+                self.num_infeasible
+            );
+        }
+        let values = [
+            ("peak_speed", self.peak.peak_speed),
+            ("peak_accel", self.peak.peak_accel),
+            ("peak_jerk", self.peak.peak_jerk),
+            ("peak_turn_rate", self.peak.peak_turn_rate),
+        ];
+        for &(key, value) in values.iter() {
+            params.insert(key.to_string(), ConstantParam::Float(value));
+        }
+        params.insert(
+            "infeasible_trajectories".to_string(),
+            ConstantParam::Int(self.num_infeasible as i64),
+        );
+    }
+}
+
+/// A directory of CSV logs of recorded trajectories, with `t` and `x` columns, and also `y` for 2D scenarios.
+/// The logs may be sampled irregularly; they are resampled to the scenario resolution.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RecordedTrajectorySpec {
+    pub directory: String,
+    /// Only use the part of each log after this time
+    #[serde(default)]
+    pub crop_start: Option<Seconds>,
+    /// Only use the part of each log before this time
+    #[serde(default)]
+    pub crop_end: Option<Seconds>,
+    /// Shift each trajectory so that it starts at the origin
+    #[serde(default = "default_rezero")]
+    pub rezero: bool,
+}
+
+fn default_rezero() -> bool {
+    true
+}
+
+/// Vectors which can be read from columns of a recorded trajectory log
+trait RecordedVector: Vector {
+    fn columns() -> &'static [&'static str];
+    fn from_columns(values: &[f64]) -> Self;
+}
+
+impl RecordedVector for Metres {
+    fn columns() -> &'static [&'static str] {
+        &["x"]
+    }
+
+    fn from_columns(values: &[f64]) -> Self {
+        values[0]
+    }
+}
+
+impl RecordedVector for Metres2D {
+    fn columns() -> &'static [&'static str] {
+        &["x", "y"]
+    }
+
+    fn from_columns(values: &[f64]) -> Self {
+        Metres2D {
+            x: values[0],
+            y: values[1],
+        }
+    }
+}
+
+impl RecordedVector for Metres3D {
+    fn columns() -> &'static [&'static str] {
+        &["x", "y", "z"]
+    }
+
+    fn from_columns(values: &[f64]) -> Self {
+        Metres3D::new(values[0], values[1], values[2])
+    }
+}
+
+impl RecordedTrajectorySpec {
+    /// Loads every CSV file in the directory, in order of file name, returning the file names along with the
+    /// cropped and resampled trajectories.
+    /// Trajectories shorter than `length` stay at their final position until then.
+    fn load<S: RecordedVector>(
+        &self,
+        length: Seconds,
+        resolution: Seconds,
+    ) -> Result<Vec<(String, trajectory::NaiveTrajectory<S>)>> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&self.directory)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<::std::result::Result<_, _>>()?;
+        files.retain(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .map_or(false, |ext| ext.eq_ignore_ascii_case("csv"))
+        });
+        files.sort();
+        ensure!(
+            !files.is_empty(),
+            "no CSV files found in {}",
+            self.directory
+        );
+
+        let mut trajectories = Vec::with_capacity(files.len());
+        for file in files {
+            let points = load_recorded_points::<S>(&file)?;
+            let mut points =
+                trajectory::crop_points(&points, self.crop_start, self.crop_end, self.rezero);
+            let (end_time, end_pos) = points[points.len() - 1];
+            if end_time < length {
+                points.push((length, end_pos));
+            }
+            let name = file
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or(format_err!("weird characters in filename"))?
+                .to_string();
+            trajectories.push((
+                name,
+                trajectory::NaiveTrajectory::from_points(resolution, points),
+            ));
+        }
+        Ok(trajectories)
+    }
+
+    /// Splits the recorded trajectories into sets, reusing them in order if there are not enough.
+    /// The files used and the cropping are recorded in the parameters of each set.
+    fn trajectory_sets<S: RecordedVector>(
+        &self,
+        length: Seconds,
+        resolution: Seconds,
+        num_sets: usize,
+        num_per_set: usize,
+    ) -> Result<TrajectorySets<trajectory::NaiveTrajectory<S>>> {
+        let recorded = self.load::<S>(length, resolution)?;
+        if recorded.len() < num_sets * num_per_set {
+            warn!(
+                "Only {} recorded trajectories for {} sets of {}, reusing them",
+                recorded.len(),
+                num_sets,
+                num_per_set
+            );
+        }
+        let mut recorded_iter = recorded.iter().cycle();
+        let mut trajectory_sets = Vec::with_capacity(num_sets);
+        for _ in 0..num_sets {
+            let (names, set): (Vec<String>, Vec<trajectory::NaiveTrajectory<S>>) =
+                recorded_iter.by_ref().take(num_per_set).cloned().unzip();
+            let mut params: Params = HashMap::with_capacity(3);
+            params.insert(
+                "recorded_files".to_string(),
+                ConstantParam::Text(names.join(",")),
+            );
+            if let Some(start) = self.crop_start {
+                params.insert("crop_start".to_string(), ConstantParam::Float(start));
+            }
+            if let Some(end) = self.crop_end {
+                params.insert("crop_end".to_string(), ConstantParam::Float(end));
+            }
+            trajectory_sets.push((params, set));
+        }
+        Ok(trajectory_sets)
+    }
+}
+
+/// Reads the `t` column and the position columns of a recorded trajectory log
+fn load_recorded_points<S: RecordedVector>(path: &Path) -> Result<Vec<(Seconds, S)>> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let headers = reader.headers()?.clone();
+    let find_column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.trim() == name)
+            .ok_or(format_err!("no {} column in {:?}", name, path))
+    };
+    let time_column = find_column("t")?;
+    let position_columns: Vec<usize> = S::columns()
+        .iter()
+        .map(|name| find_column(name))
+        .collect::<Result<_>>()?;
+
+    let mut points: Vec<(Seconds, S)> = Vec::new();
+    let mut values = vec![0.; position_columns.len()];
+    for record in reader.records() {
+        let record = record?;
+        let parse = |column: usize| -> Result<f64> {
+            let field = record
+                .get(column)
+                .ok_or(format_err!("missing field in {:?}", path))?;
+            Ok(field.trim().parse::<f64>()?)
+        };
+        let time = parse(time_column)?;
+        for (value, &column) in values.iter_mut().zip(position_columns.iter()) {
+            *value = parse(column)?;
+        }
+        if let Some(&(last_time, _)) = points.last() {
+            ensure!(
+                time > last_time,
+                "times in {:?} must be strictly increasing",
+                path
+            );
+        }
+        points.push((time, S::from_columns(&values)));
+    }
+    ensure!(!points.is_empty(), "{:?} contains no points", path);
+    Ok(points)
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DatasetDescription {
+    pub num_robots: usize,
+    pub dimensions: usize,
+    pub record_heading: bool,
+    pub topology: simulation::Topology,
+    pub points_per_trajectory: usize,
+    pub resolution: f64,
+    pub features: Params,
+    pub controller: ControllerSpec,
+    pub sensor: SensorSpec,
+    pub overrides: Vec<RobotOverrideSpec>,
+    /// Robots missed by the observer are NaN, with `x{i}_valid` recording which positions were observed
+    pub observer: ObserverSpec,
+    /// Sensor readings and estimates are recorded as `x{i}_sensed_{axis}` and `x{i}_estimated_{axis}`
+    pub estimator: Option<EstimatorSpec>,
+    /// The magnitudes of each robot's disturbances and the wind direction are recorded with each trajectory
+    pub disturbances: Option<DisturbanceSpec>,
+    /// Each recorded channel is written as `x{i}_{channel}_{axis}`, and as CSV columns `r{i}_{channel}_{axis}`
+    pub record: simulation::Recording,
+    pub files: Vec<DataFileDescription>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DataFileDescription {
+    pub file: String,
+    pub num_trajectories: usize,
     pub features: Params,
     pub per_trajectory_features: Vec<Params>,
 }
@@ -283,146 +1279,196 @@ impl ScenarioExecutionContext {
 
         self.description.num_robots = spec.robot.num_robots;
         self.description.dimensions = spec.dimensions;
+        self.description.record_heading = spec.records_heading();
+        self.description.topology = spec.topology;
         self.description.points_per_trajectory = (spec.length / spec.resolution) as usize + 1;
         self.description.resolution = spec.resolution;
         self.description.controller = spec.robot.controller.clone();
-
-        ensure!(
-            spec.robot.num_robots == 2,
-            "currently only 2 robots are supported"
-        );
+        self.description.sensor = spec.robot.sensor.clone();
+        self.description.overrides = spec.robot.overrides.clone();
+        self.description.observer = spec.observer.clone();
+        self.description.estimator = spec.estimator.clone();
+        self.description.disturbances = spec.disturbances.clone();
+        self.description.record = spec.record;
+
+        ensure!(spec.robot.num_robots >= 2, "at least 2 robots are needed");
+        if let simulation::Topology::Tree { branching } = spec.topology {
+            ensure!(branching > 0, "trees must branch at least once");
+        }
         ensure!(
-            spec.reference_trajectories.generator == "simple",
-            "only simple trajectory generation supported"
+            spec.record_heading != Some(true) || spec.dimensions == 2,
+            "headings can only be recorded in 2 dimensions"
         );
-        let num_sets = spec.reference_trajectories.num_sets;
-        let num_per_set = spec.reference_trajectories.num_per_set;
+        if let Some(ref augmentation) = spec.augmentation {
+            augmentation.validate(spec.dimensions, true)?;
+        }
+        if let Some(ref estimator) = spec.estimator {
+            estimator.validate()?;
+        }
         let mut rng = SmallRng::from_entropy();
+        let robot_generator = RobotGenerator::new(&spec.robot, &spec.observer, spec.dimensions)?;
+        let max_speed = spec.robot.max_speed;
 
         match spec.dimensions {
             1 => {
-                let mut trajectory_sets: TrajectorySets<Metres> = Vec::with_capacity(num_sets);
-                for _ in 0..num_sets {
-                    let params = spec.reference_trajectories.params.specialise(&mut rng);
-                    let generator = TrajectoryGeneratorSimple1D::from_params(&params)?;
-                    let mut set: Vec<trajectory::NaiveTrajectory<Metres>> =
-                        Vec::with_capacity(num_per_set);
-                    for _ in 0..num_per_set {
-                        match spec.override_trajectory {
-                            Some(OverrideTrajSpec::OneD(ref path)) => {
-                                set.push(trajectory::NaiveTrajectory::from_points(
-                                    spec.resolution,
-                                    path.clone(),
-                                ));
-                            }
-                            _ => {
-                                set.push(generator.generate(
-                                    spec.length,
-                                    spec.resolution,
-                                    spec.robot.max_speed,
-                                ));
-                            }
-                        }
-                    }
-                    trajectory_sets.push((params, set));
-                }
+                let override_points = match spec.override_trajectory {
+                    Some(OverrideTrajSpec::OneD(ref path)) => Some(path),
+                    _ => None,
+                };
+                let trajectory_sets = spec
+                    .reference_trajectories
+                    .trajectory_sets::<_, _, TrajectoryGenerator1D, _>(
+                        spec.length,
+                        spec.resolution,
+                        spec.robot.max_speed,
+                        override_points,
+                        &mut rng,
+                    )?;
 
-                let controller = spec
-                    .robot
-                    .controller
-                    .get_1d_controller(spec.robot.max_speed);
+                let build_robot = |controller: &ControllerSpec, sensor: &SensorSpec| -> Result<_> {
+                    Ok((
+                        controller.get_1d_controller(max_speed)?,
+                        sensor.get_1d_sensor(),
+                    ))
+                };
 
                 let generator = Simple1DFormationGenerator::new(&spec.formations.params)?;
 
-                let sensor = simulation::SharpIrSensor::new();
-
-                let observer = spec.observer.get_observer();
+                let build_observer = |observer: &ObserverSpec| observer.get_observer::<Metres>();
 
                 self.stage2(
                     spec,
                     trajectory_sets,
-                    controller,
+                    robot_generator,
+                    build_robot,
                     generator,
-                    sensor,
-                    observer,
+                    build_observer,
                 )
             }
             2 => {
-                let mut trajectory_sets: TrajectorySets<Metres2D> = Vec::with_capacity(num_sets);
-                for _ in 0..num_sets {
-                    let params = spec.reference_trajectories.params.specialise(&mut rng);
-                    let generator = TrajectoryGeneratorSimple2D::from_params(&params)?;
-                    let mut set: Vec<trajectory::NaiveTrajectory<Metres2D>> =
-                        Vec::with_capacity(num_per_set);
-                    for _ in 0..num_per_set {
-                        match spec.override_trajectory {
-                            Some(OverrideTrajSpec::TwoD(ref path)) => {
-                                set.push(trajectory::NaiveTrajectory::from_points(
-                                    spec.resolution,
-                                    path.clone(),
-                                ));
-                            }
-                            _ => {
-                                set.push(generator.generate(
-                                    spec.length,
-                                    spec.resolution,
-                                    spec.robot.max_speed,
-                                ));
-                            }
-                        }
-                    }
-                    trajectory_sets.push((params, set));
-                }
+                let override_points = match spec.override_trajectory {
+                    Some(OverrideTrajSpec::TwoD(ref path)) => Some(path),
+                    _ => None,
+                };
 
-                let controller = spec
-                    .robot
-                    .controller
-                    .get_2d_controller(spec.robot.max_speed);
+                let build_robot = |controller: &ControllerSpec, sensor: &SensorSpec| -> Result<_> {
+                    Ok((
+                        controller.get_2d_controller(max_speed)?,
+                        sensor.get_2d_sensor(),
+                    ))
+                };
 
                 // todo formation generator
 
-                let sensor = simulation::CombinedIrEncoderSensor::new();
-
-                let observer = spec.observer.get_observer();
+                let build_observer = |observer: &ObserverSpec| observer.get_observer::<Metres2D>();
 
                 let generator = Simple2DFormationGenerator::new(&spec.formations.params)?;
 
+                if spec.records_heading() {
+                    let trajectory_sets: TrajectorySets<trajectory::Heading2DTrajectory> = spec
+                        .reference_trajectories
+                        .trajectory_sets::<_, _, TrajectoryGenerator2D, _>(
+                            spec.length,
+                            spec.resolution,
+                            spec.robot.max_speed,
+                            override_points,
+                            &mut rng,
+                        )?;
+                    self.stage2(
+                        spec,
+                        trajectory_sets,
+                        robot_generator,
+                        build_robot,
+                        generator,
+                        build_observer,
+                    )
+                } else {
+                    let trajectory_sets: TrajectorySets<trajectory::NaiveTrajectory<_>> = spec
+                        .reference_trajectories
+                        .trajectory_sets::<_, _, TrajectoryGenerator2D, _>(
+                            spec.length,
+                            spec.resolution,
+                            spec.robot.max_speed,
+                            override_points,
+                            &mut rng,
+                        )?;
+                    self.stage2(
+                        spec,
+                        trajectory_sets,
+                        robot_generator,
+                        build_robot,
+                        generator,
+                        build_observer,
+                    )
+                }
+            }
+            3 => {
+                let override_points = match spec.override_trajectory {
+                    Some(OverrideTrajSpec::ThreeD(ref path)) => Some(path),
+                    _ => None,
+                };
+                let trajectory_sets = spec
+                    .reference_trajectories
+                    .trajectory_sets::<_, _, TrajectoryGeneratorSimple3D, _>(
+                        spec.length,
+                        spec.resolution,
+                        spec.robot.max_speed,
+                        override_points,
+                        &mut rng,
+                    )?;
+
+                let build_robot = |controller: &ControllerSpec, sensor: &SensorSpec| -> Result<_> {
+                    Ok((
+                        controller.get_3d_controller(max_speed)?,
+                        sensor.get_3d_sensor(),
+                    ))
+                };
+
+                let build_observer = |observer: &ObserverSpec| observer.get_observer::<Metres3D>();
+
+                let generator = Simple3DFormationGenerator::new(&spec.formations.params)?;
+
                 self.stage2(
                     spec,
                     trajectory_sets,
-                    controller,
+                    robot_generator,
+                    build_robot,
                     generator,
-                    sensor,
-                    observer,
+                    build_observer,
                 )
             }
-            _ => bail!("Only 1D and 2D operation supported"),
+            _ => bail!("Only 1D, 2D and 3D operation supported"),
         }
     }
 
     fn stage2<
         S: Vector,
+        T: trajectory::UniformResolutionTrajectory<S>,
         C: simulation::Controller<S>,
         F: simulation::Formation<S>,
         G: FormationGenerator<S, Result = F>,
         Se: simulation::DistanceSensor<S>,
+        B: Fn(&ControllerSpec, &SensorSpec) -> Result<(C, Se)>,
         O: simulation::Observer<S>,
+        BO: Fn(&ObserverSpec) -> O,
     >(
         &mut self,
         spec: &ScenarioSpec,
-        trajectory_sets: TrajectorySets<S>,
-        controller: C,
+        trajectory_sets: TrajectorySets<T>,
+        robot_generator: RobotGenerator,
+        build_robot: B,
         formation_generator: G,
-        sensor: Se,
-        mut observer: O,
+        build_observer: BO,
     ) -> Result<()> {
         let num_sets = trajectory_sets.len();
         let set_num_width = num_sets.to_string().len();
         let mut rng = SmallRng::from_entropy();
-        let num_robots = spec.robot.num_robots;
-        let sensors = vec![sensor; num_robots];
-        let controllers = vec![controller; num_robots];
         let mut total_path_error = 0.;
+        let num_copies = 1 + spec.augmentation.as_ref().map_or(0, |a| a.copies);
+        let disturbance_generator = match spec.disturbances {
+            Some(ref disturbances) => Some(DisturbanceGenerator::new(disturbances)?),
+            None => None,
+        };
         let csv_dir = self.data_dir.join("csv");
         if spec.output_csv {
             std::fs::create_dir_all(&csv_dir)?;
@@ -440,86 +1486,144 @@ impl ScenarioExecutionContext {
                 .to_string();
             file_description.features = params;
             file_description.num_trajectories =
-                spec.reference_trajectories.num_per_set * spec.robot.num_robots;
+                spec.reference_trajectories.num_per_set * spec.robot.num_robots * num_copies;
 
             let data_file = File::create(file_path)?;
             let mut writer = tf_record::ResultsWriter::<File, S>::from_writer(data_file)?;
+            if robot_generator.has_dropout() {
+                writer = writer.with_validity_mask();
+            }
 
             for (tnum, trajectory) in trajectories.into_iter().enumerate() {
                 let (formation_params, formation) =
                     formation_generator.generate(&mut rng, spec.robot.num_robots);
+                let robots = robot_generator.generate(&mut rng);
+                let disturbances = disturbance_generator
+                    .as_ref()
+                    .map(|generator| generator.generate(&mut rng, spec.robot.num_robots));
                 for leader in 0..spec.robot.num_robots {
-                    let result =
-                        simulation::SimpleSimulation::new(
-                            spec.robot.num_robots,
-                            leader,
-                            sensors.clone(),
-                            controllers.clone(),
-                            &formation,
-                            &trajectory,
-                            spec.leader_mode,
-                        ).run(spec.length, spec.resolution, &mut observer);
-
-                    if spec.output_csv {
-                        let csv_file_name = format!(
-                            "data{:0width$}_{}_l{}.csv",
-                            set_num,
-                            tnum,
-                            leader,
-                            width = set_num_width
-                        );
-                        let csv_file_path = csv_dir.join(csv_file_name);
-                        let mut writer = csv::Writer::from_path(csv_file_path)?;
-                        let res2 = result.clone();
-                        let t_res = res2.time_step();
-                        let num_robots = res2.num_robots();
-                        let csv_data = res2.into_data();
-                        let mut iterators: Vec<_> =
-                            csv_data.iter().map(|r_data| r_data.iter()).collect();
-                        let mut temp_record: Vec<String> = Vec::with_capacity(1 + num_robots * 2);
-                        // write header
-                        temp_record.push("t".to_string());
-                        for robot_id in 0..num_robots {
-                            temp_record.push(format!("r{}_x", robot_id));
-                            if spec.dimensions > 1 {
-                                temp_record.push(format!("r{}_y", robot_id));
-                            }
+                    let (controllers, sensors): (Vec<C>, Vec<Se>) = robots
+                        .iter()
+                        .map(|&(ref controller, ref sensor)| build_robot(controller, sensor))
+                        .collect::<Result<Vec<_>>>()?
+                        .into_iter()
+                        .unzip();
+                    let simulation = simulation::SimpleSimulation::new(
+                        spec.robot.num_robots,
+                        leader,
+                        sensors,
+                        controllers,
+                        &formation,
+                        &trajectory,
+                        spec.leader_mode,
+                    ).with_topology(spec.topology);
+                    let parents = simulation.parents().to_vec();
+                    let simulation = if spec.records_heading() {
+                        simulation.record_headings()
+                    } else {
+                        simulation
+                    };
+                    let simulation = simulation.with_recording(spec.record);
+                    let simulation = match spec.estimator {
+                        Some(EstimatorSpec::Kalman(params)) => simulation.with_estimator(params),
+                        None => simulation,
+                    };
+                    let simulation = match disturbances {
+                        Some(ref disturbances) => {
+                            simulation.with_disturbances(disturbances.clone())
                         }
-                        writer.write_record(&temp_record)?;
-                        temp_record.clear();
-                        let mut cur_t = 0.;
-                        'record: loop {
-                            temp_record.push(cur_t.to_string());
-                            for robot_data_iter in iterators.iter_mut() {
-                                let maybe_data = robot_data_iter.next();
-                                if let Some(&pos) = maybe_data {
-                                    let strings = pos.repr().into_iter().map(|v| v.to_string());
-                                    temp_record.extend(strings);
-                                } else {
-                                    break 'record; //end of iter
-                                }
-                            }
-                            writer.write_record(&temp_record)?;
-                            temp_record.clear();
-                            cur_t += t_res;
-                        }
-                    }
-
-                    let mut trajectory_params = formation_params.clone();
-                    trajectory_params
-                        .insert("leader".to_string(), ConstantParam::Int(leader as i64));
+                        None => simulation,
+                    };
+                    let observers = robot_generator
+                        .observers
+                        .iter()
+                        .map(&build_observer)
+                        .collect();
+                    let result = simulation.run(spec.length, spec.resolution, observers);
                     if let Some(error) = result.path_error() {
-                        trajectory_params
-                            .insert("path_error".to_string(), ConstantParam::Float(error));
                         total_path_error += error;
                         trace!("Path error={}", error);
                     }
 
-                    file_description
-                        .per_trajectory_features
-                        .push(trajectory_params);
+                    let transforms = augmentation_transforms(
+                        &spec.augmentation,
+                        &mut rng,
+                        spec.robot.num_robots,
+                        spec.dimensions,
+                    );
+                    for (copy, transform) in transforms.iter().enumerate() {
+                        let result = transform.result(result.clone());
 
-                    writer.write_record(result, leader)?;
+                        if spec.output_csv {
+                            let copy_suffix = if copy > 0 {
+                                format!("_a{}", copy)
+                            } else {
+                                String::new()
+                            };
+                            let csv_file_name = format!(
+                                "data{:0width$}_{}_l{}{}.csv",
+                                set_num,
+                                tnum,
+                                leader,
+                                copy_suffix,
+                                width = set_num_width
+                            );
+                            write_result_csv(&csv_dir.join(csv_file_name), result.clone())?;
+                        }
+
+                        // the file name keeps the simulated leader, so copies do not collide
+                        let leader = transform.robot_index(leader);
+                        let mut trajectory_params = formation_params.clone();
+                        trajectory_params
+                            .insert("leader".to_string(), ConstantParam::Int(leader as i64));
+                        if let Some(AugmentationSpec {
+                            time_reverse: true, ..
+                        }) = spec.augmentation
+                        {
+                            // reversed copies swap the causal roles of the leader and followers
+                            trajectory_params.insert(
+                                "time_reversed".to_string(),
+                                ConstantParam::Int(transform.time_reversed as i64),
+                            );
+                        }
+                        if let Some(error) = result.path_error() {
+                            trajectory_params
+                                .insert("path_error".to_string(), ConstantParam::Float(error));
+                        }
+                        if spec.topology != simulation::Topology::Star {
+                            let parents: Vec<String> = transform
+                                .parents(&parents)
+                                .iter()
+                                .map(|p| p.to_string())
+                                .collect();
+                            trajectory_params.insert(
+                                "parents".to_string(),
+                                ConstantParam::Text(parents.join(",")),
+                            );
+                        }
+                        if !robot_generator.uniform {
+                            robot_generator.record(&robots, transform, &mut trajectory_params);
+                        }
+                        if let Some(ref disturbances) = disturbances {
+                            DisturbanceGenerator::record(
+                                disturbances,
+                                transform,
+                                &mut trajectory_params,
+                            );
+                        }
+                        record_transform(&mut trajectory_params, &spec.augmentation, transform);
+
+                        file_description
+                            .per_trajectory_features
+                            .push(trajectory_params);
+
+                        let record_meta = if spec.augmentation.is_some() {
+                            transform.features()
+                        } else {
+                            HashMap::new()
+                        };
+                        writer.write_record_with_meta(result, leader, record_meta)?;
+                    }
                 }
             }
 
@@ -546,100 +1650,708 @@ impl ScenarioExecutionContext {
         write!(&mut info_file, "# datagen-info v2.1\n")?;
         serde_yaml::to_writer(&mut info_file, &self.description)?;
 
-        info!("Finished writing file {:?}", self.info_file_path);
-        Ok(())
+        info!("Finished writing file {:?}", self.info_file_path);
+        Ok(())
+    }
+
+    fn get_prefix() -> String {
+        time::strftime("dg-%Y_%m_%d-%H_%M_%S-", &time::now()).unwrap()
+    }
+}
+
+/// Writes a simulation result as CSV, one row per step
+fn write_result_csv<S: Vector, R: SimulationResult<S>>(path: &Path, result: R) -> Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    let t_res = result.time_step();
+    let num_robots = result.num_robots();
+    let headings = result.headings().cloned();
+    let channels = result.channels().to_vec();
+    let csv_data = result.into_data();
+    let mut iterators: Vec<_> = csv_data.iter().map(|r_data| r_data.iter()).collect();
+    let mut heading_iterators: Vec<_> = headings
+        .iter()
+        .flat_map(|h| h.iter().map(|r_headings| r_headings.iter()))
+        .collect();
+    let mut channel_iterators: Vec<_> = channels
+        .iter()
+        .flat_map(|c| c.data.iter().map(|r_data| r_data.iter()))
+        .collect();
+    let mut temp_record: Vec<String> = Vec::with_capacity(1 + num_robots * 3);
+    // write header
+    temp_record.push("t".to_string());
+    for robot_id in 0..num_robots {
+        for axis in ["x", "y", "z"].iter().take(S::repr_length()) {
+            temp_record.push(format!("r{}_{}", robot_id, axis));
+        }
+    }
+    for robot_id in 0..heading_iterators.len() {
+        temp_record.push(format!("r{}_heading", robot_id));
+    }
+    for channel in channels.iter() {
+        for robot_id in 0..channel.data.len() {
+            for axis in ["x", "y", "z"].iter().take(S::repr_length()) {
+                temp_record.push(format!("r{}_{}_{}", robot_id, channel.name, axis));
+            }
+        }
+    }
+    writer.write_record(&temp_record)?;
+    temp_record.clear();
+    let mut cur_t = 0.;
+    'record: loop {
+        temp_record.push(cur_t.to_string());
+        for robot_data_iter in iterators.iter_mut() {
+            let maybe_data = robot_data_iter.next();
+            if let Some(&pos) = maybe_data {
+                let strings = pos.repr().into_iter().map(|v| v.to_string());
+                temp_record.extend(strings);
+            } else {
+                break 'record; //end of iter
+            }
+        }
+        for heading_iter in heading_iterators.iter_mut() {
+            if let Some(heading) = heading_iter.next() {
+                temp_record.push(heading.to_string());
+            } else {
+                break 'record;
+            }
+        }
+        for channel_iter in channel_iterators.iter_mut() {
+            if let Some(&v) = channel_iter.next() {
+                temp_record.extend(v.repr().into_iter().map(|v| v.to_string()));
+            } else {
+                break 'record;
+            }
+        }
+        writer.write_record(&temp_record)?;
+        temp_record.clear();
+        cur_t += t_res;
+    }
+    Ok(())
+}
+
+impl ScenarioSpec {
+    pub fn execute(&self) -> Result<PathBuf> {
+        let mut executor = ScenarioExecutionContext::new();
+        executor.execute(self)?;
+        Ok(executor.into_info_file_path())
+    }
+
+    /// Headings are recorded in 2 dimensions unless `record_heading` is turned off
+    fn records_heading(&self) -> bool {
+        self.record_heading.unwrap_or(self.dimensions == 2)
+    }
+}
+
+/// Reads the optional speed distribution parameters of the simple generators, using the defaults for any not given
+fn speed_distribution_from_params(params: &Params) -> Result<trajectory::SpeedDistribution> {
+    let default = trajectory::SpeedDistribution::default();
+    let get = |key: &str, default: f64| -> Result<f64> {
+        params.get(key).map_or(Ok(default), |param| param.as_f64())
+    };
+    let speeds = trajectory::SpeedDistribution {
+        p_stationary: get("p_stationary", default.p_stationary)?,
+        p_full_speed: get("p_full_speed", default.p_full_speed)?,
+        p_forward: get("p_forward", default.p_forward)?,
+        random_speed_mean: get("random_speed_mean", default.random_speed_mean)?,
+        random_speed_sd: get("random_speed_sd", default.random_speed_sd)?,
+    };
+    speeds.validate().map_err(|err| format_err!("{}", err))?;
+    Ok(speeds)
+}
+
+fn smoothing_from_params(params: &Params, spline: bool) -> Result<Option<f64>> {
+    if !spline {
+        return Ok(None);
+    }
+    let smoothing = params
+        .get("smoothing")
+        .map_or(Ok(0.), |param| param.as_f64())?;
+    ensure!(smoothing >= 0., "smoothing must not be negative");
+    Ok(Some(smoothing))
+}
+
+/// Records the speed distribution parameters actually used, so that defaulted ones also appear in the description
+fn record_speed_distribution(speeds: &trajectory::SpeedDistribution, params: &mut Params) {
+    let values = [
+        ("p_stationary", speeds.p_stationary),
+        ("p_full_speed", speeds.p_full_speed),
+        ("p_forward", speeds.p_forward),
+        ("random_speed_mean", speeds.random_speed_mean),
+        ("random_speed_sd", speeds.random_speed_sd),
+    ];
+    for &(key, value) in values.iter() {
+        params.insert(key.to_string(), ConstantParam::Float(value));
+    }
+}
+
+/// Generates reference trajectories of type `T` from the parameters of a trajectory set
+trait TrajectoryGenerator<T>: Sized {
+    /// `generator` is the name of the generator given in the scenario
+    fn from_params(generator: &str, params: &Params) -> Result<Self>;
+
+    fn generate(&self, length: Seconds, resolution: Seconds, max_speed: MetresPerSecond) -> T;
+
+    /// Adds any parameters which were not given but had default values to `params`, so that they are recorded
+    fn record_params(&self, _params: &mut Params) {}
+}
+
+/// Whether the simple generators should produce splines. `generator` must be `simple` or `spline`.
+fn is_spline_generator(generator: &str) -> Result<bool> {
+    match generator {
+        "simple" => Ok(false),
+        "spline" => Ok(true),
+        other => bail!("unknown trajectory generator {}", other),
+    }
+}
+
+fn record_smoothing(smoothing: Option<f64>, params: &mut Params) {
+    if let Some(smoothing) = smoothing {
+        params.insert("smoothing".to_string(), ConstantParam::Float(smoothing));
+    }
+}
+
+struct TrajectoryGeneratorSimple1D {
+    variability: f64,
+    rsd: f64,
+    speeds: trajectory::SpeedDistribution,
+    /// Smoothing of the spline through the control points, or None for piecewise linear trajectories
+    smoothing: Option<f64>,
+}
+
+impl TrajectoryGenerator<trajectory::NaiveTrajectory<Metres>> for TrajectoryGeneratorSimple1D {
+    /// With the `spline` generator the trajectories are smooth splines rather than piecewise linear,
+    /// with the smoothing given by the optional `smoothing` param.
+    fn from_params(generator: &str, params: &Params) -> Result<Self> {
+        let variability = params
+            .get("variability")
+            .ok_or(format_err!("variability param not found"))?
+            .as_f64()?;
+        let rsd = params
+            .get("rsd")
+            .ok_or(format_err!("rsd param not found"))?
+            .as_f64()?;
+        let speeds = speed_distribution_from_params(params)?;
+        let smoothing = smoothing_from_params(params, is_spline_generator(generator)?)?;
+        Ok(TrajectoryGeneratorSimple1D {
+            variability,
+            rsd,
+            speeds,
+            smoothing,
+        })
+    }
+
+    fn generate(
+        &self,
+        length: Seconds,
+        resolution: Seconds,
+        max_speed: MetresPerSecond,
+    ) -> trajectory::NaiveTrajectory<Metres> {
+        let points = trajectory::generate_1d_trajectory_points_simple(
+            max_speed,
+            length,
+            self.variability,
+            self.rsd,
+            &self.speeds,
+        );
+        match self.smoothing {
+            Some(smoothing) => {
+                trajectory::NaiveTrajectory::from_spline(resolution, &points, smoothing)
+            }
+            None => trajectory::NaiveTrajectory::from_points(resolution, points),
+        }
+    }
+
+    fn record_params(&self, params: &mut Params) {
+        record_speed_distribution(&self.speeds, params);
+        record_smoothing(self.smoothing, params);
+    }
+}
+
+struct TrajectoryGeneratorSimple2D {
+    variability: f64,
+    rsd: f64,
+    turnability: f64,
+    speeds: trajectory::SpeedDistribution,
+    /// Smoothing of the spline through the control points, or None for piecewise linear trajectories
+    smoothing: Option<f64>,
+}
+
+impl TrajectoryGeneratorSimple2D {
+    fn new(generator: &str, params: &Params) -> Result<Self> {
+        {
+            let variability = params
+                .get("variability")
+                .ok_or(format_err!("variability param not found"))?
+                .as_f64()?;
+            let rsd = params
+                .get("rsd")
+                .ok_or(format_err!("rsd param not found"))?
+                .as_f64()?;
+            let turnability = params
+                .get("turnability")
+                .ok_or(format_err!("turnability param not found"))?
+                .as_f64()?;
+            let speeds = speed_distribution_from_params(params)?;
+            let smoothing = smoothing_from_params(params, is_spline_generator(generator)?)?;
+            Ok(TrajectoryGeneratorSimple2D {
+                variability,
+                rsd,
+                turnability,
+                speeds,
+                smoothing,
+            })
+        }
+    }
+
+    fn record(&self, params: &mut Params) {
+        record_speed_distribution(&self.speeds, params);
+        record_smoothing(self.smoothing, params);
+    }
+}
+
+impl TrajectoryGenerator<trajectory::NaiveTrajectory<Metres2D>> for TrajectoryGeneratorSimple2D {
+    fn from_params(generator: &str, params: &Params) -> Result<Self> {
+        Self::new(generator, params)
+    }
+
+    fn generate(
+        &self,
+        length: Seconds,
+        resolution: Seconds,
+        max_speed: MetresPerSecond,
+    ) -> trajectory::NaiveTrajectory<Metres2D> {
+        let points = trajectory::generate_2d_trajectory_points_simple(
+            max_speed,
+            length,
+            self.variability,
+            self.rsd,
+            self.turnability,
+            &self.speeds,
+        );
+        match self.smoothing {
+            Some(smoothing) => {
+                trajectory::NaiveTrajectory::from_spline(resolution, &points, smoothing)
+            }
+            None => trajectory::NaiveTrajectory::from_points(resolution, points),
+        }
+    }
+
+    fn record_params(&self, params: &mut Params) {
+        self.record(params);
+    }
+}
+
+/// Oriented trajectories, where the simple generator also gives the heading of the leader.
+/// Spline trajectories take their heading from their direction of motion.
+impl TrajectoryGenerator<trajectory::Heading2DTrajectory> for TrajectoryGeneratorSimple2D {
+    fn from_params(generator: &str, params: &Params) -> Result<Self> {
+        Self::new(generator, params)
+    }
+
+    fn generate(
+        &self,
+        length: Seconds,
+        resolution: Seconds,
+        max_speed: MetresPerSecond,
+    ) -> trajectory::Heading2DTrajectory {
+        if self.smoothing.is_some() {
+            let positions: trajectory::NaiveTrajectory<Metres2D> =
+                self.generate(length, resolution, max_speed);
+            return positions.into();
+        }
+        let points = trajectory::generate_2d_trajectory_points_with_heading(
+            max_speed,
+            length,
+            self.variability,
+            self.rsd,
+            self.turnability,
+            &self.speeds,
+        );
+        trajectory::Heading2DTrajectory::from_points(resolution, points)
+    }
+
+    fn record_params(&self, params: &mut Params) {
+        self.record(params);
+    }
+}
+
+/// The 3D versions of the `simple` and `spline` generators. As well as the params of the 2D generators,
+/// the `climb_rate` param is the maximum rate of climb or descent as a fraction of the maximum speed.
+struct TrajectoryGeneratorSimple3D {
+    horizontal: TrajectoryGeneratorSimple2D,
+    climb_rate: f64,
+}
+
+impl TrajectoryGenerator<trajectory::NaiveTrajectory<Metres3D>> for TrajectoryGeneratorSimple3D {
+    fn from_params(generator: &str, params: &Params) -> Result<Self> {
+        let horizontal = TrajectoryGeneratorSimple2D::new(generator, params)?;
+        let climb_rate = param_f64(params, "climb_rate")?;
+        ensure!(climb_rate >= 0., "climb rate must not be negative");
+        Ok(TrajectoryGeneratorSimple3D {
+            horizontal,
+            climb_rate,
+        })
+    }
+
+    fn generate(
+        &self,
+        length: Seconds,
+        resolution: Seconds,
+        max_speed: MetresPerSecond,
+    ) -> trajectory::NaiveTrajectory<Metres3D> {
+        let points = trajectory::generate_3d_trajectory_points_simple(
+            max_speed,
+            length,
+            self.horizontal.variability,
+            self.horizontal.rsd,
+            self.horizontal.turnability,
+            self.climb_rate * max_speed,
+            &self.horizontal.speeds,
+        );
+        match self.horizontal.smoothing {
+            Some(smoothing) => {
+                trajectory::NaiveTrajectory::from_spline(resolution, &points, smoothing)
+            }
+            None => trajectory::NaiveTrajectory::from_points(resolution, points),
+        }
+    }
+
+    fn record_params(&self, params: &mut Params) {
+        self.horizontal.record(params);
+    }
+}
+
+/// Reads a required float parameter
+fn param_f64(params: &Params, key: &str) -> Result<f64> {
+    params
+        .get(key)
+        .ok_or(format_err!("{} param not found", key))?
+        .as_f64()
+}
+
+/// Velocities follow an Ornstein–Uhlenbeck process. The `velocity_sd` param, and in 1D the optional
+/// `velocity_mean` param, are fractions of the maximum speed, and the `time_constant` param is in seconds.
+struct TrajectoryGeneratorOU {
+    velocity: trajectory::OrnsteinUhlenbeck,
+}
+
+impl TrajectoryGeneratorOU {
+    fn new(params: &Params, dimensions: usize) -> Result<Self> {
+        let mean = if dimensions == 1 {
+            params
+                .get("velocity_mean")
+                .map_or(Ok(0.), |param| param.as_f64())?
+        } else {
+            ensure!(
+                !params.contains_key("velocity_mean"),
+                "velocity_mean is only supported in 1 dimension"
+            );
+            0.
+        };
+        let velocity = trajectory::OrnsteinUhlenbeck {
+            mean,
+            sd: param_f64(params, "velocity_sd")?,
+            time_constant: param_f64(params, "time_constant")?,
+        };
+        velocity.validate().map_err(|err| format_err!("{}", err))?;
+        Ok(TrajectoryGeneratorOU { velocity })
+    }
+
+    /// The velocity process in metres per second
+    fn velocity(&self, max_speed: MetresPerSecond) -> trajectory::OrnsteinUhlenbeck {
+        trajectory::OrnsteinUhlenbeck {
+            mean: self.velocity.mean * max_speed,
+            sd: self.velocity.sd * max_speed,
+            ..self.velocity
+        }
     }
+}
 
-    fn get_prefix() -> String {
-        time::strftime("dg-%Y_%m_%d-%H_%M_%S-", &time::now()).unwrap()
+/// A correlated random walk in speed and heading. The `speed_mean` and `speed_sd` params are fractions of the
+/// maximum speed, `turn_rate_sd` is in radians per second, and `speed_time_constant` and `turn_time_constant`
+/// are in seconds.
+struct TrajectoryGeneratorCRW {
+    speed: trajectory::OrnsteinUhlenbeck,
+    turn_rate: trajectory::OrnsteinUhlenbeck,
+}
+
+impl TrajectoryGeneratorCRW {
+    fn new(params: &Params) -> Result<Self> {
+        let speed = trajectory::OrnsteinUhlenbeck {
+            mean: param_f64(params, "speed_mean")?,
+            sd: param_f64(params, "speed_sd")?,
+            time_constant: param_f64(params, "speed_time_constant")?,
+        };
+        let turn_rate = trajectory::OrnsteinUhlenbeck {
+            mean: 0.,
+            sd: param_f64(params, "turn_rate_sd")?,
+            time_constant: param_f64(params, "turn_time_constant")?,
+        };
+        speed
+            .validate()
+            .map_err(|err| format_err!("speed {}", err))?;
+        turn_rate
+            .validate()
+            .map_err(|err| format_err!("turn rate {}", err))?;
+        Ok(TrajectoryGeneratorCRW { speed, turn_rate })
+    }
+
+    fn generate_points(
+        &self,
+        length: Seconds,
+        resolution: Seconds,
+        max_speed: MetresPerSecond,
+    ) -> Vec<(Seconds, Metres2D, Radians)> {
+        let speed = trajectory::OrnsteinUhlenbeck {
+            mean: self.speed.mean * max_speed,
+            sd: self.speed.sd * max_speed,
+            ..self.speed
+        };
+        trajectory::generate_2d_trajectory_points_crw(
+            max_speed,
+            length,
+            resolution,
+            &speed,
+            &self.turn_rate,
+        )
     }
 }
 
-impl ScenarioSpec {
-    pub fn execute(&self) -> Result<PathBuf> {
-        let mut executor = ScenarioExecutionContext::new();
-        executor.execute(self)?;
-        Ok(executor.into_info_file_path())
+/// Deterministic patterns, with the generator named after the pattern. The `period` param is required, and
+/// the optional `amplitude`, `phase`, `radius` and `ratio` params are recorded with their defaults if not given.
+struct TrajectoryGeneratorPattern {
+    pattern: trajectory::Pattern,
+    params: trajectory::PatternParams,
+}
+
+impl TrajectoryGeneratorPattern {
+    fn new(pattern: trajectory::Pattern, params: &Params) -> Result<Self> {
+        let default = trajectory::PatternParams::default();
+        let get = |key: &str, default: f64| -> Result<f64> {
+            params.get(key).map_or(Ok(default), |param| param.as_f64())
+        };
+        let pattern_params = trajectory::PatternParams {
+            period: param_f64(params, "period")?,
+            amplitude: get("amplitude", default.amplitude)?,
+            phase: get("phase", default.phase)?,
+            radius: get("radius", default.radius)?,
+            ratio: get("ratio", default.ratio)?,
+        };
+        pattern
+            .validate(&pattern_params)
+            .map_err(|err| format_err!("{}", err))?;
+        Ok(TrajectoryGeneratorPattern {
+            pattern,
+            params: pattern_params,
+        })
+    }
+
+    fn record(&self, params: &mut Params) {
+        let values = [
+            ("amplitude", self.params.amplitude),
+            ("phase", self.params.phase),
+            ("radius", self.params.radius),
+            ("ratio", self.params.ratio),
+        ];
+        for &(key, value) in values.iter() {
+            params.insert(key.to_string(), ConstantParam::Float(value));
+        }
     }
 }
 
-struct TrajectoryGeneratorSimple1D {
-    variability: f64,
-    rsd: f64,
+/// The 1D trajectory generators, chosen by name: `simple`, `spline`, `ornstein_uhlenbeck`,
+/// or a pattern which moves along a line
+enum TrajectoryGenerator1D {
+    Simple(TrajectoryGeneratorSimple1D),
+    OrnsteinUhlenbeck(TrajectoryGeneratorOU),
+    Pattern(TrajectoryGeneratorPattern),
 }
 
-impl TrajectoryGeneratorSimple1D {
-    pub fn from_params(params: &Params) -> Result<Self> {
-        let variability = params
-            .get("variability")
-            .ok_or(format_err!("variability param not found"))?
-            .as_f64()?;
-        let rsd = params
-            .get("rsd")
-            .ok_or(format_err!("rsd param not found"))?
-            .as_f64()?;
-        Ok(TrajectoryGeneratorSimple1D { variability, rsd })
+impl TrajectoryGenerator<trajectory::NaiveTrajectory<Metres>> for TrajectoryGenerator1D {
+    fn from_params(generator: &str, params: &Params) -> Result<Self> {
+        if let Some(pattern) = trajectory::Pattern::from_name(generator) {
+            ensure!(
+                pattern.is_1d(),
+                "the {} pattern is only supported in 2 dimensions",
+                generator
+            );
+            let pattern = TrajectoryGeneratorPattern::new(pattern, params)?;
+            return Ok(TrajectoryGenerator1D::Pattern(pattern));
+        }
+        Ok(match generator {
+            "ornstein_uhlenbeck" => {
+                TrajectoryGenerator1D::OrnsteinUhlenbeck(TrajectoryGeneratorOU::new(params, 1)?)
+            }
+            "correlated_random_walk" => {
+                bail!("the correlated_random_walk generator is only supported in 2 dimensions")
+            }
+            _ => TrajectoryGenerator1D::Simple(TrajectoryGeneratorSimple1D::from_params(
+                generator, params,
+            )?),
+        })
     }
 
-    pub fn generate(
+    fn generate(
         &self,
         length: Seconds,
         resolution: Seconds,
         max_speed: MetresPerSecond,
     ) -> trajectory::NaiveTrajectory<Metres> {
-        let points = trajectory::generate_1d_trajectory_points_simple(
-            max_speed,
-            length,
-            self.variability,
-            self.rsd,
-        );
-        trajectory::NaiveTrajectory::from_points(resolution, points)
+        match *self {
+            TrajectoryGenerator1D::Simple(ref generator) => {
+                generator.generate(length, resolution, max_speed)
+            }
+            TrajectoryGenerator1D::OrnsteinUhlenbeck(ref generator) => {
+                let points = trajectory::generate_1d_trajectory_points_ou(
+                    max_speed,
+                    length,
+                    resolution,
+                    &generator.velocity(max_speed),
+                );
+                trajectory::NaiveTrajectory::from_points(resolution, points)
+            }
+            TrajectoryGenerator1D::Pattern(ref generator) => {
+                // only 1D patterns are accepted in `from_params`
+                let points = trajectory::generate_1d_trajectory_points_pattern(
+                    generator.pattern,
+                    &generator.params,
+                    length,
+                    resolution,
+                ).expect("not a 1D pattern");
+                trajectory::NaiveTrajectory::from_points(resolution, points)
+            }
+        }
+    }
+
+    fn record_params(&self, params: &mut Params) {
+        match *self {
+            TrajectoryGenerator1D::Simple(ref generator) => generator.record_params(params),
+            TrajectoryGenerator1D::Pattern(ref generator) => generator.record(params),
+            TrajectoryGenerator1D::OrnsteinUhlenbeck(ref generator) => {
+                params.insert(
+                    "velocity_mean".to_string(),
+                    ConstantParam::Float(generator.velocity.mean),
+                );
+            }
+        }
     }
 }
 
-struct TrajectoryGeneratorSimple2D {
-    variability: f64,
-    rsd: f64,
-    turnability: f64,
+/// The 2D trajectory generators, chosen by name: `simple`, `spline`, `ornstein_uhlenbeck`,
+/// `correlated_random_walk` or a pattern
+enum TrajectoryGenerator2D {
+    Simple(TrajectoryGeneratorSimple2D),
+    OrnsteinUhlenbeck(TrajectoryGeneratorOU),
+    CorrelatedRandomWalk(TrajectoryGeneratorCRW),
+    Pattern(TrajectoryGeneratorPattern),
 }
 
-impl TrajectoryGeneratorSimple2D {
-    pub fn from_params(params: &Params) -> Result<Self> {
-        {
-            let variability = params
-                .get("variability")
-                .ok_or(format_err!("variability param not found"))?
-                .as_f64()?;
-            let rsd = params
-                .get("rsd")
-                .ok_or(format_err!("rsd param not found"))?
-                .as_f64()?;
-            let turnability = params
-                .get("turnability")
-                .ok_or(format_err!("turnability param not found"))?
-                .as_f64()?;
-            Ok(TrajectoryGeneratorSimple2D {
-                variability,
-                rsd,
-                turnability,
-            })
+impl TrajectoryGenerator2D {
+    fn new(generator: &str, params: &Params) -> Result<Self> {
+        if let Some(pattern) = trajectory::Pattern::from_name(generator) {
+            let pattern = TrajectoryGeneratorPattern::new(pattern, params)?;
+            return Ok(TrajectoryGenerator2D::Pattern(pattern));
+        }
+        Ok(match generator {
+            "ornstein_uhlenbeck" => {
+                TrajectoryGenerator2D::OrnsteinUhlenbeck(TrajectoryGeneratorOU::new(params, 2)?)
+            }
+            "correlated_random_walk" => {
+                TrajectoryGenerator2D::CorrelatedRandomWalk(TrajectoryGeneratorCRW::new(params)?)
+            }
+            _ => {
+                TrajectoryGenerator2D::Simple(TrajectoryGeneratorSimple2D::new(generator, params)?)
+            }
+        })
+    }
+
+    fn record(&self, params: &mut Params) {
+        match *self {
+            TrajectoryGenerator2D::Simple(ref generator) => generator.record(params),
+            TrajectoryGenerator2D::Pattern(ref generator) => generator.record(params),
+            _ => {}
         }
     }
+}
+
+impl TrajectoryGenerator<trajectory::NaiveTrajectory<Metres2D>> for TrajectoryGenerator2D {
+    fn from_params(generator: &str, params: &Params) -> Result<Self> {
+        Self::new(generator, params)
+    }
 
-    pub fn generate(
+    fn generate(
         &self,
         length: Seconds,
         resolution: Seconds,
         max_speed: MetresPerSecond,
     ) -> trajectory::NaiveTrajectory<Metres2D> {
-        let points = trajectory::generate_2d_trajectory_points_simple(
-            max_speed,
-            length,
-            self.variability,
-            self.rsd,
-            self.turnability,
-        );
-        trajectory::NaiveTrajectory::from_points(resolution, points)
+        match *self {
+            TrajectoryGenerator2D::Simple(ref generator) => {
+                generator.generate(length, resolution, max_speed)
+            }
+            TrajectoryGenerator2D::OrnsteinUhlenbeck(ref generator) => {
+                let points = trajectory::generate_2d_trajectory_points_ou(
+                    max_speed,
+                    length,
+                    resolution,
+                    &generator.velocity(max_speed),
+                );
+                trajectory::NaiveTrajectory::from_points(resolution, points)
+            }
+            TrajectoryGenerator2D::CorrelatedRandomWalk(ref generator) => {
+                let points: Vec<(Seconds, Metres2D)> = generator
+                    .generate_points(length, resolution, max_speed)
+                    .into_iter()
+                    .map(|(t, pos, _)| (t, pos))
+                    .collect();
+                trajectory::NaiveTrajectory::from_points(resolution, points)
+            }
+            TrajectoryGenerator2D::Pattern(ref generator) => {
+                let points = trajectory::generate_2d_trajectory_points_pattern(
+                    generator.pattern,
+                    &generator.params,
+                    length,
+                    resolution,
+                );
+                trajectory::NaiveTrajectory::from_points(resolution, points)
+            }
+        }
+    }
+
+    fn record_params(&self, params: &mut Params) {
+        self.record(params);
+    }
+}
+
+impl TrajectoryGenerator<trajectory::Heading2DTrajectory> for TrajectoryGenerator2D {
+    fn from_params(generator: &str, params: &Params) -> Result<Self> {
+        Self::new(generator, params)
+    }
+
+    fn generate(
+        &self,
+        length: Seconds,
+        resolution: Seconds,
+        max_speed: MetresPerSecond,
+    ) -> trajectory::Heading2DTrajectory {
+        match *self {
+            TrajectoryGenerator2D::Simple(ref generator) => {
+                generator.generate(length, resolution, max_speed)
+            }
+            TrajectoryGenerator2D::CorrelatedRandomWalk(ref generator) => {
+                let points = generator.generate_points(length, resolution, max_speed);
+                trajectory::Heading2DTrajectory::from_points(resolution, points)
+            }
+            TrajectoryGenerator2D::OrnsteinUhlenbeck(_) | TrajectoryGenerator2D::Pattern(_) => {
+                let positions: trajectory::NaiveTrajectory<Metres2D> =
+                    self.generate(length, resolution, max_speed);
+                positions.into()
+            }
+        }
+    }
+
+    fn record_params(&self, params: &mut Params) {
+        self.record(params);
     }
 }
 
@@ -649,6 +2361,14 @@ trait FormationGenerator<S: Vector> {
     fn generate<R: Rng + ?Sized>(&self, rng: &mut R, num_robots: usize) -> (Params, Self::Result);
 }
 
+/// Robots in a line, `spacing` apart, with the last robot at the origin
+fn line_formation<S: Vector>(num_robots: usize, spacing: S) -> simulation::SimpleFormation<S> {
+    let positions = (0..num_robots)
+        .map(|i| spacing * (num_robots - 1 - i) as f64)
+        .collect();
+    simulation::SimpleFormation::new(num_robots, S::zero(), positions)
+}
+
 enum GenericFloatParam {
     Constant(f64),
     Uniform(Uniform<f64>),
@@ -664,6 +2384,7 @@ impl GenericFloatParam {
             {
                 ConstantParam::Float(val) => Ok(GenericFloatParam::Constant(val)),
                 ConstantParam::Int(val) => Ok(GenericFloatParam::Constant(val as f64)),
+                ConstantParam::Text(_) => bail!("parameter {} must be a number", key),
             },
             ParamsSpec::Random { ref values } => {
                 match *values
@@ -718,11 +2439,10 @@ impl FormationGenerator<Metres> for Simple1DFormationGenerator {
     type Result = simulation::SimpleFormation<Metres>;
 
     fn generate<R: Rng + ?Sized>(&self, rng: &mut R, num_robots: usize) -> (Params, Self::Result) {
-        assert_eq!(num_robots, 2);
         let distance = self.distance.sample(rng);
         (
             Self::get_params(distance),
-            simulation::SimpleFormation::new(2, 0., vec![distance, 0.]),
+            line_formation(num_robots, distance),
         )
     }
 }
@@ -752,22 +2472,51 @@ impl FormationGenerator<Metres2D> for Simple2DFormationGenerator {
     type Result = simulation::SimpleFormation<Metres2D>;
 
     fn generate<R: Rng + ?Sized>(&self, rng: &mut R, num_robots: usize) -> (Params, Self::Result) {
-        assert_eq!(num_robots, 2);
         let distance_x = self.distance_x.sample(rng);
         let distance_y = self.distance_y.sample(rng);
         (
             Self::get_params(distance_x, distance_y),
-            simulation::SimpleFormation::new(
-                2,
-                Metres2D::zero(),
-                vec![
-                    Metres2D {
-                        x: distance_x,
-                        y: distance_y,
-                    },
-                    Metres2D::zero(),
-                ],
-            ),
+            line_formation(num_robots, Metres2D::new(distance_x, distance_y)),
+        )
+    }
+}
+
+struct Simple3DFormationGenerator {
+    distance_x: GenericFloatParam,
+    distance_y: GenericFloatParam,
+    distance_z: GenericFloatParam,
+}
+
+impl Simple3DFormationGenerator {
+    fn new(params: &ParamsSpec) -> Result<Self> {
+        Ok(Simple3DFormationGenerator {
+            distance_x: GenericFloatParam::from_param(params, "distance_x")?,
+            distance_y: GenericFloatParam::from_param(params, "distance_y")?,
+            distance_z: GenericFloatParam::from_param(params, "distance_z")?,
+        })
+    }
+
+    fn get_params(distance: Metres3D) -> Params {
+        let mut params = HashMap::with_capacity(3);
+        params.insert("distance_x".to_string(), ConstantParam::Float(distance.x));
+        params.insert("distance_y".to_string(), ConstantParam::Float(distance.y));
+        params.insert("distance_z".to_string(), ConstantParam::Float(distance.z));
+        params
+    }
+}
+
+impl FormationGenerator<Metres3D> for Simple3DFormationGenerator {
+    type Result = simulation::SimpleFormation<Metres3D>;
+
+    fn generate<R: Rng + ?Sized>(&self, rng: &mut R, num_robots: usize) -> (Params, Self::Result) {
+        let distance = Metres3D::new(
+            self.distance_x.sample(rng),
+            self.distance_y.sample(rng),
+            self.distance_z.sample(rng),
+        );
+        (
+            Self::get_params(distance),
+            line_formation(num_robots, distance),
         )
     }
 }
@@ -796,6 +2545,16 @@ pub struct GenericScenarioSpec {
     pub add_noise: bool,
     pub speed_limit_factor: Option<f64>,
     pub override_trajectory: Option<MultiDubinsPath>,
+    /// YAML or CSV file of waypoints to build the leader path from, instead of generating it randomly
+    #[serde(default)]
+    pub waypoints_file: Option<String>,
+    /// Relative weights of the path types used for generated leader paths.
+    /// Path types not listed are never used. If empty, each leg takes the shortest path.
+    #[serde(default)]
+    pub path_types: HashMap<DubinsPathType, f64>,
+    /// Time reversal is not allowed, as the robots only drive forwards
+    #[serde(default)]
+    pub augmentation: Option<AugmentationSpec>,
 }
 
 impl GenericScenarioSpec {
@@ -804,6 +2563,27 @@ impl GenericScenarioSpec {
     }
 }
 
+/// Loads a list of waypoints from a YAML or CSV file, depending on its extension.
+/// CSV files need a header with at least the `x`, `y` and `r` columns, and may also have `path_type` and `speed` columns.
+pub fn load_waypoints<P: AsRef<Path>>(path: P) -> Result<Vec<Waypoint>> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_lowercase());
+    let waypoints: Vec<Waypoint> = match extension.as_ref().map(|ext| ext.as_str()) {
+        Some("yaml") | Some("yml") => serde_yaml::from_reader(File::open(path)?)?,
+        Some("csv") => {
+            let mut reader = csv::Reader::from_path(path)?;
+            let records: ::std::result::Result<Vec<Waypoint>, csv::Error> =
+                reader.deserialize().collect();
+            records?
+        }
+        _ => bail!("waypoint file {:?} must be a YAML or CSV file", path),
+    };
+    Ok(waypoints)
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct DesaiRobotSpec {
     id: String,
@@ -985,15 +2765,50 @@ impl GenericScenarioExecutionContext {
         );
         features.insert("origin_x".to_string(), ConstantParam::Float(spec.origin.x));
         features.insert("origin_y".to_string(), ConstantParam::Float(spec.origin.y));
+        for (path_type, weight) in spec.path_types.iter() {
+            features.insert(
+                format!("path_type_weight_{:?}", path_type),
+                ConstantParam::Float(*weight),
+            );
+        }
         self.description.features = features;
 
+        ensure!(
+            spec.path_types.values().all(|&weight| weight >= 0.)
+                && (spec.path_types.is_empty()
+                    || spec.path_types.values().any(|&weight| weight > 0.)),
+            "path type weights must be non-negative, and at least one must be positive"
+        );
+        let path_types: Vec<(DubinsPathType, f64)> =
+            spec.path_types.iter().map(|(&k, &v)| (k, v)).collect();
+        if let Some(ref augmentation) = spec.augmentation {
+            augmentation.validate(2, false)?;
+        }
+        let num_copies = 1 + spec.augmentation.as_ref().map_or(0, |a| a.copies);
+
+        ensure!(
+            spec.override_trajectory.is_none() || spec.waypoints_file.is_none(),
+            "only one of override_trajectory and waypoints_file may be given"
+        );
+        let override_trajectory = match spec.waypoints_file {
+            Some(ref file) => {
+                let waypoints = load_waypoints(file)?;
+                Some(MultiDubinsPath::from_waypoints(
+                    &waypoints,
+                    spec.turning_radius,
+                    spec.speed,
+                )?)
+            }
+            None => spec.override_trajectory,
+        };
+
         let mut rng = thread_rng();
         let turning_radius = spec.turning_radius;
         let speed = spec.speed;
         let min_length = spec.length;
         let arena_size = spec.arena_size;
         let mut traj_generator: Box<FnMut(OrientedPosition2D) -> MultiDubinsPath> =
-            match spec.override_trajectory {
+            match override_trajectory {
                 None => Box::new(|initial: OrientedPosition2D| {
                     MultiDubinsPath::generate(
                         turning_radius,
@@ -1002,6 +2817,7 @@ impl GenericScenarioExecutionContext {
                         &mut rng,
                         initial,
                         arena_size,
+                        &path_types,
                     ).expect("could not generate leader path")
                 }),
                 Some(path) => {
@@ -1039,16 +2855,31 @@ impl GenericScenarioExecutionContext {
                 .to_str()
                 .ok_or(format_err!("weird characters in filename"))?
                 .to_string();
-            file_description.num_trajectories = spec.num_per_configuration;
+            file_description.num_trajectories = spec.num_per_configuration * num_copies;
 
             let data_file = File::create(file_path)?;
             let mut writer = tf_record::GenericTfWriter::from_writer(data_file);
 
             for idx in 0..spec.num_per_configuration {
                 let spec_type = spec.spec_type;
+                let mut leader_words: Vec<(String, String)> = Vec::new();
                 let robots: Vec<NonHolonomicRobotSpec> = configuration
                     .iter()
-                    .map(|c| c.to_real_spec(traj_generator.deref_mut(), spec_type))
+                    .map(|c| {
+                        c.to_real_spec(
+                            |initial| {
+                                let path = traj_generator(initial);
+                                let words: Vec<String> = path
+                                    .words()
+                                    .iter()
+                                    .map(|word| format!("{:?}", word))
+                                    .collect();
+                                leader_words.push((c.id.clone(), words.join("-")));
+                                path
+                            },
+                            spec_type,
+                        )
+                    })
                     .collect();
 
                 let (mut results, path_err) = simulation_2d::do_desai_simulation(
@@ -1059,9 +2890,11 @@ impl GenericScenarioExecutionContext {
                     spec.add_noise,
                     spec.length,
                 );
-                let mut pt_params: Params = HashMap::with_capacity(1);
+                let mut pt_params: Params = HashMap::with_capacity(2 + leader_words.len());
                 pt_params.insert("path_err_sqd".to_string(), ConstantParam::Float(path_err));
-                file_description.per_trajectory_features.push(pt_params);
+                for (id, words) in leader_words {
+                    pt_params.insert(format!("words_{}", id), ConstantParam::Text(words));
+                }
 
                 total_path_err_sq += path_err;
 
@@ -1072,54 +2905,89 @@ impl GenericScenarioExecutionContext {
                     .map(|name| results.remove(name).unwrap())
                     .collect();
 
-                if spec.output_csv {
-                    let csv_file_name = format!(
-                        "{}_{:0width$}.csv",
-                        file_slug,
-                        idx,
-                        width = per_config_width
+                let transforms = augmentation_transforms(
+                    &spec.augmentation,
+                    &mut thread_rng(),
+                    self.description.num_robots,
+                    2,
+                );
+                for (copy, transform) in transforms.iter().enumerate() {
+                    let per_robot_data = transform.reorder(
+                        per_robot_data
+                            .iter()
+                            .map(|r_data| {
+                                r_data
+                                    .iter()
+                                    .map(|&(t, ref dynamics)| (t, transform.dynamics(dynamics)))
+                                    .collect()
+                            })
+                            .collect(),
                     );
-                    let csv_file_path = csv_dir.join(csv_file_name);
-                    let mut writer = csv::Writer::from_path(csv_file_path)?;
-
-                    let mut iterators: Vec<_> =
-                        per_robot_data.iter().map(|r_data| r_data.iter()).collect();
-                    let mut temp_record: Vec<String> =
-                        Vec::with_capacity(1 + self.description.num_robots * 3);
-                    // write header
-                    temp_record.push("t".to_string());
-                    for robot_id in 0..self.description.num_robots {
-                        temp_record.push(format!("r{}_x", robot_id));
-                        temp_record.push(format!("r{}_y", robot_id));
-                        temp_record.push(format!("r{}_r", robot_id));
-                    }
-                    writer.write_record(&temp_record)?;
-                    temp_record.clear();
-                    'record: loop {
-                        for (robot_id, robot_data_iter) in iterators.iter_mut().enumerate() {
-                            let maybe_data = robot_data_iter.next();
-                            if let Some(&(t, ref dynamics)) = maybe_data {
-                                if robot_id == 0 {
-                                    temp_record.push(t.to_string());
-                                }
-                                temp_record.push(dynamics.position.x.to_string());
-                                temp_record.push(dynamics.position.y.to_string());
-                                temp_record.push(dynamics.heading.to_string());
-                            } else {
-                                break 'record; //end of iter
-                            }
+                    let mut pt_params = pt_params.clone();
+                    record_transform(&mut pt_params, &spec.augmentation, transform);
+                    file_description.per_trajectory_features.push(pt_params);
+
+                    if spec.output_csv {
+                        let copy_suffix = if copy > 0 {
+                            format!("_a{}", copy)
+                        } else {
+                            String::new()
+                        };
+                        let csv_file_name = format!(
+                            "{}_{:0width$}{}.csv",
+                            file_slug,
+                            idx,
+                            copy_suffix,
+                            width = per_config_width
+                        );
+                        let csv_file_path = csv_dir.join(csv_file_name);
+                        let mut writer = csv::Writer::from_path(csv_file_path)?;
+
+                        let mut iterators: Vec<_> =
+                            per_robot_data.iter().map(|r_data| r_data.iter()).collect();
+                        let mut temp_record: Vec<String> =
+                            Vec::with_capacity(1 + self.description.num_robots * 3);
+                        // write header
+                        temp_record.push("t".to_string());
+                        for robot_id in 0..self.description.num_robots {
+                            temp_record.push(format!("r{}_x", robot_id));
+                            temp_record.push(format!("r{}_y", robot_id));
+                            temp_record.push(format!("r{}_r", robot_id));
                         }
                         writer.write_record(&temp_record)?;
                         temp_record.clear();
+                        'record: loop {
+                            for (robot_id, robot_data_iter) in iterators.iter_mut().enumerate() {
+                                let maybe_data = robot_data_iter.next();
+                                if let Some(&(t, ref dynamics)) = maybe_data {
+                                    if robot_id == 0 {
+                                        temp_record.push(t.to_string());
+                                    }
+                                    temp_record.push(dynamics.position.x.to_string());
+                                    temp_record.push(dynamics.position.y.to_string());
+                                    temp_record.push(dynamics.heading.to_string());
+                                } else {
+                                    break 'record; //end of iter
+                                }
+                            }
+                            writer.write_record(&temp_record)?;
+                            temp_record.clear();
+                        }
                     }
-                }
 
-                let mut features: HashMap<String, tf_record::TfFeature> = HashMap::with_capacity(1);
-                features.insert(
-                    "leaders".to_string(),
-                    tf_record::TfFeature::Ints(leader_ids.clone()),
-                );
-                writer.write_record(features, per_robot_data)?;
+                    let mut features: HashMap<String, tf_record::TfFeature> =
+                        if spec.augmentation.is_some() {
+                            transform.features()
+                        } else {
+                            HashMap::with_capacity(1)
+                        };
+                    let leaders = leader_ids
+                        .iter()
+                        .map(|&id| transform.robot_index(id as usize) as i64)
+                        .collect();
+                    features.insert("leaders".to_string(), tf_record::TfFeature::Ints(leaders));
+                    writer.write_record(features, per_robot_data)?;
+                }
             }
 
             file_description.configuration = configuration;
@@ -1146,3 +3014,309 @@ impl GenericScenarioExecutionContext {
         time::strftime("gdg-%Y_%m_%d-%H_%M_%S-", &time::now()).unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num::Zero;
+    use simulation::Formation;
+    use trajectory::Trajectory;
+
+    #[test]
+    fn waypoints_csv() {
+        let dir = std::env::temp_dir().join(format!("datagen-waypoints-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let good = dir.join("good.csv");
+        File::create(&good)
+            .unwrap()
+            .write_all(b"x,y,r,path_type,speed\n0,0,0,,\n5,5,1.5,LSR,0.5\n10,0,0,,\n")
+            .unwrap();
+        let bad = dir.join("bad.CSV");
+        File::create(&bad)
+            .unwrap()
+            .write_all(b"x,y,r\n0,0,0\n5,five,1.5\n")
+            .unwrap();
+        let waypoints = load_waypoints(&good);
+        let malformed = load_waypoints(&bad);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let waypoints = waypoints.unwrap();
+        assert_eq!(waypoints.len(), 3);
+        assert!(waypoints[0].path_type.is_none() && waypoints[0].speed.is_none());
+        match waypoints[1].path_type {
+            Some(DubinsPathType::LSR) => {}
+            other => panic!("unexpected path type {:?}", other),
+        }
+        assert_eq!((waypoints[1].x, waypoints[1].r), (5., 1.5));
+        assert_eq!(waypoints[1].speed, Some(0.5));
+        assert!(waypoints[2].path_type.is_none() && waypoints[2].speed.is_none());
+        assert!(malformed.is_err());
+    }
+
+    #[test]
+    fn recorded_trajectory_sets() {
+        let dir = std::env::temp_dir().join(format!("datagen-recorded-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        File::create(dir.join("a.csv"))
+            .unwrap()
+            .write_all(b"t,x,y\n1.0,1.0,1.0\n1.3,2.0,1.0\n2.5,2.0,3.0\n")
+            .unwrap();
+        File::create(dir.join("b.csv"))
+            .unwrap()
+            .write_all(b"y,t,x\n0,0,0\n0,1,1\n")
+            .unwrap();
+        File::create(dir.join("notes.txt")).unwrap();
+
+        let spec = RecordedTrajectorySpec {
+            directory: dir.to_str().unwrap().to_string(),
+            crop_start: None,
+            crop_end: Some(2.),
+            rezero: true,
+        };
+        let sets: TrajectorySets<trajectory::NaiveTrajectory<Metres2D>> =
+            spec.trajectory_sets(1.5, 0.5, 2, 1).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(sets.len(), 2);
+        let (ref params, ref set) = sets[0];
+        match params["recorded_files"] {
+            ConstantParam::Text(ref files) => assert_eq!(files, "a.csv"),
+            ref other => panic!("unexpected {:?}", other),
+        }
+        // resampled at 0.5s up to the crop at 2s in the log, starting at the origin, then held until 1.5s
+        let data = set[0].data();
+        assert_eq!(data.len(), 4);
+        assert_eq!(data[3], data[2]);
+        assert_eq!(data[0], Metres2D::zero());
+        assert!((data[1].x - 1.).abs() < 1e-9 && (data[1].y - 1. / 3.).abs() < 1e-9);
+        assert_eq!(sets[1].1[0].data().len(), 4);
+    }
+
+    #[test]
+    fn oriented_trajectory_sets() {
+        let values = ["variability", "rsd", "turnability"]
+            .iter()
+            .map(|name| (name.to_string(), ConstantParam::Float(1.)))
+            .collect();
+        let spec = ReferenceTrajectorySpec {
+            num_sets: 1,
+            num_per_set: 2,
+            generator: "simple".to_string(),
+            params: ParamsSpec::Constant { values },
+            recorded: None,
+            limits: None,
+        };
+        let points = vec![
+            (0., Metres2D::new(0., 0.)),
+            (1., Metres2D::new(0., 1.)),
+            (2., Metres2D::new(0., 1.)),
+        ];
+        let mut rng = SmallRng::from_entropy();
+        let sets = spec
+            .trajectory_sets::<_, trajectory::Heading2DTrajectory, TrajectoryGeneratorSimple2D, _>(
+                2.,
+                0.5,
+                1.,
+                Some(&points),
+                &mut rng,
+            ).unwrap();
+        assert_eq!(sets[0].1.len(), 2);
+        let trajectory = &sets[0].1[0];
+        assert_eq!(trajectory[1], Metres2D::new(0., 0.5));
+        // heading follows the direction of motion and is kept while stationary
+        let heading = trajectory.heading_at_time(1.).unwrap();
+        assert!((heading - std::f64::consts::FRAC_PI_2).abs() < 1e-9);
+        assert_eq!(trajectory.heading_at_time(2.), Some(heading));
+    }
+
+    #[test]
+    fn kinematically_limited_trajectory_sets() {
+        let values = ["variability", "rsd"]
+            .iter()
+            .map(|name| (name.to_string(), ConstantParam::Float(1.)))
+            .collect();
+        let mut limits = KinematicLimitsSpec {
+            max_accel: Some(1.),
+            max_jerk: None,
+            max_turn_rate: None,
+            enforce: false,
+            reject_infeasible: false,
+        };
+        let mut spec = ReferenceTrajectorySpec {
+            num_sets: 1,
+            num_per_set: 1,
+            generator: "simple".to_string(),
+            params: ParamsSpec::Constant { values },
+            recorded: None,
+            limits: Some(limits.clone()),
+        };
+        // starts moving instantly
+        let points = vec![(0., 0.), (1., 1.)];
+        let mut rng = SmallRng::from_entropy();
+        let mut generate = |spec: &ReferenceTrajectorySpec| {
+            spec.trajectory_sets::<_, trajectory::NaiveTrajectory<_>, TrajectoryGenerator1D, _>(
+                1.,
+                0.5,
+                1.,
+                Some(&points),
+                &mut rng,
+            )
+        };
+
+        let sets = generate(&spec).unwrap();
+        let params = &sets[0].0;
+        assert_eq!(params["peak_accel"].as_f64().unwrap(), 2.);
+        match params["infeasible_trajectories"] {
+            ConstantParam::Int(count) => assert_eq!(count, 1),
+            ref other => panic!("unexpected {:?}", other),
+        }
+
+        limits.enforce = true;
+        spec.limits = Some(limits.clone());
+        let sets = generate(&spec).unwrap();
+        assert!(sets[0].0["peak_accel"].as_f64().unwrap() <= 1.);
+        assert_eq!(sets[0].1[0].data(), &vec![0., 0.25, 0.75]);
+
+        limits.enforce = false;
+        limits.reject_infeasible = true;
+        spec.limits = Some(limits);
+        assert!(generate(&spec).is_err());
+    }
+
+    #[test]
+    fn line_formations() {
+        let mut values = HashMap::new();
+        values.insert("distance_x".to_string(), ConstantParam::Float(1.));
+        values.insert("distance_y".to_string(), ConstantParam::Float(-0.5));
+        let generator = Simple2DFormationGenerator::new(&ParamsSpec::Constant { values }).unwrap();
+        let mut rng = SmallRng::from_entropy();
+
+        let (_, pair) = generator.generate(&mut rng, 2);
+        assert_eq!(
+            pair.positions(),
+            &[Metres2D::new(1., -0.5), Metres2D::zero()]
+        );
+
+        let (params, formation) = generator.generate(&mut rng, 4);
+        assert_eq!(params["distance_x"].as_f64().unwrap(), 1.);
+        assert_eq!(formation.num_robots(), 4);
+        assert_eq!(formation.positions()[0], Metres2D::new(3., -1.5));
+        assert_eq!(formation.positions()[2], Metres2D::new(1., -0.5));
+        assert_eq!(formation.positions()[3], Metres2D::zero());
+    }
+
+    #[test]
+    fn heterogeneous_robots() {
+        let spec: RobotSpec = serde_yaml::from_str(
+            "
+max_speed: 1.0
+num_robots: 3
+controller: {type: PID, p_gain: 1.0, i_gain: 0.0, d_gain: 0.5}
+overrides:
+  - {robot: 2, controller: {type: P, p_gain: 3.0}, sensor: {type: perfect}}
+  - {robot: 1, observer: {type: dropout, probability: 0.1}}
+gains:
+  p_gain: {dist: uniform, range: [2.0, 2.5]}
+",
+        ).unwrap();
+        let observer = ObserverSpec::Simple { error: 0.1 };
+        let generator = RobotGenerator::new(&spec, &observer, 1).unwrap();
+        assert!(!generator.uniform);
+        assert!(generator.has_dropout());
+        let robots = generator.generate(&mut SmallRng::from_entropy());
+        for &(ref controller, ref sensor) in robots[..2].iter() {
+            let gains = controller.gains();
+            assert!(gains[0].1 >= 2. && gains[0].1 <= 2.5);
+            assert_eq!(gains[2], ("d_gain", 0.5));
+            assert_eq!(sensor.name(), "sharp_ir");
+        }
+        assert_eq!(robots[2].0.gains(), vec![("p_gain", 3.)]);
+        assert_eq!(robots[2].1.name(), "perfect");
+
+        let mut params = Params::new();
+        let mut transform = Transform::identity(3, 1);
+        transform.permutation = vec![1, 2, 0];
+        generator.record(&robots, &transform, &mut params);
+        match params["r0_controller"] {
+            ConstantParam::Text(ref name) => assert_eq!(name, "P"),
+            ref other => panic!("unexpected {:?}", other),
+        }
+        match params["r2_observer"] {
+            ConstantParam::Text(ref name) => assert_eq!(name, "dropout"),
+            ref other => panic!("unexpected {:?}", other),
+        }
+
+        let mut bad = spec.clone();
+        bad.overrides[0].robot = 3;
+        assert!(RobotGenerator::new(&bad, &observer, 1).is_err());
+        bad = spec.clone();
+        bad.overrides[1].observer = Some(ObserverSpec::Bias { offset: vec![] });
+        assert!(RobotGenerator::new(&bad, &observer, 1).is_err());
+        bad = spec.clone();
+        bad.controller = ControllerSpec::P { p_gain: 1. };
+        bad.gains.insert(
+            "d_gain".to_string(),
+            RandomParamSpec::Constant { value: 1. },
+        );
+        assert!(RobotGenerator::new(&bad, &observer, 1).is_err());
+    }
+
+    #[test]
+    fn controller_dimensions() {
+        let pid2d: ControllerSpec = serde_yaml::from_str(
+            "{type: PID2D, x: {p_gain: 1.0, i_gain: 0.0, d_gain: 0.0}, y: {p_gain: 2.0, i_gain: 0.0, d_gain: 0.0}}",
+        ).unwrap();
+        assert!(pid2d.get_2d_controller(1.).is_ok());
+        assert!(pid2d.get_1d_controller(1.).is_err());
+        assert!(pid2d.get_3d_controller(1.).is_err());
+        let pid = ControllerSpec::default();
+        assert!(pid.get_1d_controller(1.).is_ok() && pid.get_3d_controller(1.).is_ok());
+    }
+
+    #[test]
+    fn disturbance_magnitudes() {
+        let spec: DisturbanceSpec = serde_yaml::from_str(
+            "
+wind: {dist: uniform, range: [0.5, 1.0]}
+impulse_rate: 0.2
+impulse: {dist: constant, value: 0.3}
+",
+        ).unwrap();
+        let generator = DisturbanceGenerator::new(&spec).unwrap();
+        let disturbances = generator.generate::<Metres2D, _>(&mut SmallRng::from_entropy(), 2);
+        for disturbance in disturbances.iter() {
+            assert!(disturbance.wind >= 0.5 && disturbance.wind <= 1.);
+            assert_eq!(disturbance.wind_direction, disturbances[0].wind_direction);
+            assert!((disturbance.wind_direction.length() - 1.).abs() < 1e-9);
+            assert_eq!(disturbance.process_noise, 0.);
+            assert_eq!(disturbance.impulse_rate, 0.2);
+            assert_eq!(disturbance.impulse, 0.3);
+        }
+
+        let mut params = Params::new();
+        let mut transform = Transform::identity(2, 2);
+        transform.permutation = vec![1, 0];
+        DisturbanceGenerator::record(&disturbances, &transform, &mut params);
+        assert_eq!(params["r0_impulse"].as_f64().unwrap(), 0.3);
+        assert_eq!(params["r0_wind"].as_f64().unwrap(), disturbances[1].wind);
+        assert_eq!(
+            params["wind_direction_y"].as_f64().unwrap(),
+            disturbances[0].wind_direction.y
+        );
+        assert!(!params.contains_key("wind_direction_z"));
+
+        // reversed copies are blown the opposite way
+        transform.time_reversed = true;
+        DisturbanceGenerator::record(&disturbances, &transform, &mut params);
+        assert_eq!(
+            params["wind_direction_x"].as_f64().unwrap(),
+            -disturbances[0].wind_direction.x
+        );
+
+        let bad = DisturbanceSpec {
+            impulse_rate: -1.,
+            ..spec
+        };
+        assert!(DisturbanceGenerator::new(&bad).is_err());
+    }
+}
@@ -88,11 +88,31 @@ pub trait ToFloatFeatures {
     fn to_float_features(&self) -> Vec<f64>;
 }
 
+#[derive(Debug, Clone)]
 pub enum TfFeature {
     Floats(Vec<f64>),
     Ints(Vec<i64>),
 }
 
+impl TfFeature {
+    fn into_feature(self) -> Feature {
+        let mut feat = Feature::new();
+        match self {
+            TfFeature::Floats(floats) => {
+                let mut list = FloatList::new();
+                list.value = floats.into_iter().map(|f| f as f32).collect();
+                feat.set_float_list(list);
+            }
+            TfFeature::Ints(ints) => {
+                let mut list = Int64List::new();
+                list.value = ints;
+                feat.set_int64_list(list);
+            }
+        }
+        feat
+    }
+}
+
 #[derive(Debug)]
 pub struct GenericTfWriter<W: Write> {
     out_stream: ZlibEncoder<W>,
@@ -125,20 +145,7 @@ impl<W: Write> GenericTfWriter<W> {
             HashMap::with_capacity(record_meta.len() + num_robots * num_robot_features);
 
         for (key, feature) in record_meta {
-            let mut feat = Feature::new();
-            match feature {
-                TfFeature::Floats(floats) => {
-                    let mut list = FloatList::new();
-                    list.value = floats.into_iter().map(|f| f as f32).collect();
-                    feat.set_float_list(list);
-                }
-                TfFeature::Ints(ints) => {
-                    let mut list = Int64List::new();
-                    list.value = ints;
-                    feat.set_int64_list(list);
-                }
-            }
-            features.insert(key, feat);
+            features.insert(key, feature.into_feature());
         }
 
         for (robot_id, robot) in robot_data.into_iter().enumerate() {
@@ -195,6 +202,7 @@ pub struct ResultsWriter<W: Write, S: Vector> {
     info: Option<DataInfo>,
     out_stream: ZlibEncoder<W>,
     file_path: Option<PathBuf>,
+    with_validity: bool,
     _marker: PhantomData<S>,
 }
 
@@ -217,6 +225,7 @@ impl<S: Vector> ResultsWriter<fs::File, S> {
             info: None,
             out_stream: ZlibEncoder::new(writer, Default::default()),
             file_path: Some(file_path),
+            with_validity: false,
             _marker: Default::default(),
         })
     }
@@ -246,19 +255,38 @@ impl<W: Write, S: Vector> ResultsWriter<W, S> {
             info: None,
             out_stream: ZlibEncoder::new(writer, Default::default()),
             file_path: None,
+            with_validity: false,
             _marker: Default::default(),
         })
     }
 
+    /// Also writes whether each position was observed, as `x{i}_valid`, with missed observations being NaN
+    pub fn with_validity_mask(mut self) -> Self {
+        self.with_validity = true;
+        self
+    }
+
     pub fn write_record<R: simulation::SimulationResult<S>>(
         &mut self,
         result: R,
         leader_number: usize,
+    ) -> TfRecordResult<()> {
+        self.write_record_with_meta(result, leader_number, HashMap::new())
+    }
+
+    /// Writes a record with additional features describing it
+    pub fn write_record_with_meta<R: simulation::SimulationResult<S>>(
+        &mut self,
+        result: R,
+        leader_number: usize,
+        record_meta: HashMap<String, TfFeature>,
     ) -> TfRecordResult<()> {
         // check that all the static info is still the same
         self.verify_info(&result)?;
 
         // construct an `Example` message
+        let headings = result.headings().cloned();
+        let channels = result.channels().to_vec();
         let data = result.into_data();
 
         // first, construct the features
@@ -270,10 +298,25 @@ impl<W: Write, S: Vector> ResultsWriter<W, S> {
         let mut leader_num_feature = Feature::new();
         leader_num_feature.set_int64_list(leader_num_list);
         features.insert("leader_number".to_string(), leader_num_feature);
+        for (key, feature) in record_meta {
+            features.insert(key, feature.into_feature());
+        }
         let features_per_robot = S::repr_length();
 
         // add the trajectory features per robot
         for (i, x) in data.into_iter().enumerate() {
+            if self.with_validity {
+                let mut valid_list = Int64List::new();
+                valid_list.set_value(
+                    x.iter()
+                        .map(|pos| simulation::is_observed(pos) as i64)
+                        .collect(),
+                );
+                let mut valid_feature = Feature::new();
+                valid_feature.set_int64_list(valid_list);
+                features.insert(format!("x{}_valid", i), valid_feature);
+            }
+
             let mut feature_lists: Vec<Vec<f32>> = vec![Vec::new(); features_per_robot];
 
             for pos in x {
@@ -293,6 +336,36 @@ impl<W: Write, S: Vector> ResultsWriter<W, S> {
             }
         }
 
+        // add the headings per robot, if recorded
+        if let Some(headings) = headings {
+            for (i, robot_headings) in headings.into_iter().enumerate() {
+                let mut feature_list = FloatList::new();
+                feature_list.set_value(robot_headings.into_iter().map(|h| h as f32).collect());
+                let mut feature = Feature::new();
+                feature.set_float_list(feature_list);
+                features.insert(format!("x{}_heading", i), feature);
+            }
+        }
+
+        // add the other recorded vectors per robot, one feature per axis
+        for channel in channels {
+            for (i, x) in channel.data.into_iter().enumerate() {
+                let mut feature_lists: Vec<Vec<f32>> = vec![Vec::new(); features_per_robot];
+                for v in x {
+                    for (j, value) in v.repr().into_iter().enumerate() {
+                        feature_lists[j].push(value as f32);
+                    }
+                }
+                for (dim, list) in feature_lists.into_iter().enumerate() {
+                    let mut feature_list = FloatList::new();
+                    feature_list.set_value(list);
+                    let mut feature = Feature::new();
+                    feature.set_float_list(feature_list);
+                    features.insert(format!("x{}_{}_{}", i, channel.name, dim), feature);
+                }
+            }
+        }
+
         // make the example
         let mut features_msg = Features::new();
         features_msg.set_feature(features);
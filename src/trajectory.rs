@@ -10,6 +10,16 @@ use std::ops::Index;
 pub struct NaiveTrajectory<S: Vector>(Seconds, Vec<S>);
 
 impl<S: Vector> NaiveTrajectory<S> {
+    /// Copies the samples of any uniform resolution trajectory
+    pub fn from_trajectory<T: UniformResolutionTrajectory<S>>(
+        trajectory: &T,
+    ) -> NaiveTrajectory<S> {
+        let resolution = trajectory.resolution();
+        let num_points = num_samples(trajectory.end_time(), resolution);
+        let data = (0..num_points).map(|index| trajectory[index]).collect();
+        NaiveTrajectory(resolution, data)
+    }
+
     pub fn time_step(&self) -> Seconds {
         self.0
     }
@@ -58,47 +68,380 @@ impl<S: Vector> NaiveTrajectory<S> {
 
         NaiveTrajectory(resolution, data)
     }
+
+    /// Creates a uniform NaiveTrajectory by sampling a cubic smoothing spline fitted to a set of control points.
+    /// The points must be ordered in strictly increasing order of time, and the first point should have time = 0.
+    pub fn from_spline(
+        resolution: Seconds,
+        points: &[(Seconds, S)],
+        smoothing: f64,
+    ) -> NaiveTrajectory<S> {
+        let spline = CubicSpline::new(points, smoothing);
+        let num_points = (spline.end_time() / resolution + 1e-9).floor() as u64 + 1;
+        let data = (0..num_points)
+            .map(|index| spline.pos_at_time(index as f64 * resolution))
+            .collect();
+        NaiveTrajectory(resolution, data)
+    }
+}
+
+/// Crops a list of irregularly timed points to the times between `start` and `end`, linearly interpolating the points at
+/// the ends, and shifts the times so that the first point is at time 0.
+/// If `rezero` is set the positions are also shifted so that the first point is at the origin.
+/// The points must be in strictly increasing order of time.
+pub fn crop_points<S: Vector>(
+    points: &[(Seconds, S)],
+    start: Option<Seconds>,
+    end: Option<Seconds>,
+    rezero: bool,
+) -> Vec<(Seconds, S)> {
+    if points.is_empty() {
+        return Vec::new();
+    }
+    let interpolate = |time: Seconds| {
+        let right = points
+            .iter()
+            .position(|&(t, _)| t >= time)
+            .unwrap_or(points.len() - 1);
+        if right == 0 || points[right].0 <= time {
+            return points[right].1;
+        }
+        let (left_time, left_pos) = points[right - 1];
+        let (right_time, right_pos) = points[right];
+        let a = (time - left_time) / (right_time - left_time);
+        left_pos * (1. - a) + right_pos * a
+    };
+    let start = start.unwrap_or(points[0].0).max(points[0].0);
+    let end = end.unwrap_or(points[points.len() - 1].0);
+    if end <= start {
+        let pos = if rezero {
+            S::zero()
+        } else {
+            interpolate(start)
+        };
+        return vec![(0., pos)];
+    }
+
+    let mut cropped = vec![(start, interpolate(start))];
+    cropped.extend(
+        points
+            .iter()
+            .filter(|&&(t, _)| t > start && t < end)
+            .cloned(),
+    );
+    if end <= points[points.len() - 1].0 {
+        cropped.push((end, interpolate(end)));
+    }
+    let origin = if rezero { cropped[0].1 } else { S::zero() };
+    cropped
+        .into_iter()
+        .map(|(t, pos)| (t - start, pos - origin))
+        .collect()
+}
+
+/// A natural cubic smoothing spline fitted to a set of control points.
+/// Its position, velocity and acceleration are all continuous.
+///
+/// `smoothing` weights the integrated squared acceleration against the squared distance from the control points:
+/// zero gives the interpolating spline, and larger values give smoother curves which pass further from the points.
+#[derive(Clone, Debug)]
+pub struct CubicSpline<S: Vector> {
+    times: Vec<Seconds>,
+    values: Vec<S>,
+    second_derivatives: Vec<S>,
 }
 
+impl<S: Vector> CubicSpline<S> {
+    /// The points must be in strictly increasing order of time
+    pub fn new(points: &[(Seconds, S)], smoothing: f64) -> CubicSpline<S> {
+        if points.is_empty() {
+            panic!("must pass in some points");
+        }
+        let n = points.len();
+        let times: Vec<Seconds> = points.iter().map(|&(t, _)| t).collect();
+        let y: Vec<S> = points.iter().map(|&(_, p)| p).collect();
+        if n < 3 {
+            return CubicSpline {
+                times,
+                values: y,
+                second_derivatives: vec![S::zero(); n],
+            };
+        }
+        let h: Vec<f64> = times.windows(2).map(|w| w[1] - w[0]).collect();
+
+        // Column j of Q (for interior knot j + 1) has entries in rows j, j + 1 and j + 2
+        let q_column = |j: usize| [1. / h[j], -1. / h[j] - 1. / h[j + 1], 1. / h[j + 1]];
+        let q_entry = |row: usize, j: usize| {
+            if row >= j && row <= j + 2 {
+                q_column(j)[row - j]
+            } else {
+                0.
+            }
+        };
+
+        // Solve (R + smoothing Q'Q) gamma = Q'y, where gamma are the second derivatives at the interior knots
+        let m = n - 2;
+        let mut matrix = vec![vec![0.; m]; m];
+        for (j, matrix_row) in matrix.iter_mut().enumerate() {
+            let band = matrix_row.iter_mut().enumerate().take(j + 3);
+            for (k, entry) in band.skip(j.saturating_sub(2)) {
+                let qtq: f64 = (0..n).map(|row| q_entry(row, j) * q_entry(row, k)).sum();
+                let r = if j == k {
+                    (h[j] + h[j + 1]) / 3.
+                } else if k == j + 1 {
+                    h[j + 1] / 6.
+                } else if j == k + 1 {
+                    h[j] / 6.
+                } else {
+                    0.
+                };
+                *entry = r + smoothing * qtq;
+            }
+        }
+        let mut rhs: Vec<S> = (0..m)
+            .map(|j| (y[j + 2] - y[j + 1]) / h[j + 1] - (y[j + 1] - y[j]) / h[j])
+            .collect();
+
+        // The matrix is symmetric positive definite with bandwidth 2, so no pivoting is needed
+        for k in 0..m {
+            let pivot_row = matrix[k].clone();
+            for row in (k + 1)..(k + 3).min(m) {
+                let factor = matrix[row][k] / pivot_row[k];
+                let band = matrix[row].iter_mut().zip(pivot_row.iter()).take(k + 3);
+                for (entry, pivot) in band.skip(k) {
+                    *entry -= factor * pivot;
+                }
+                let pivot_rhs = rhs[k];
+                rhs[row] -= pivot_rhs * factor;
+            }
+        }
+        let mut gamma = vec![S::zero(); m];
+        for k in (0..m).rev() {
+            let mut val = rhs[k];
+            for col in (k + 1)..(k + 3).min(m) {
+                val -= gamma[col] * matrix[k][col];
+            }
+            gamma[k] = val / matrix[k][k];
+        }
+
+        let values: Vec<S> = (0..n)
+            .map(|row| {
+                let mut q_gamma = S::zero();
+                let band = gamma.iter().enumerate().take(row + 1);
+                for (j, &g) in band.skip(row.saturating_sub(2)) {
+                    q_gamma += g * q_entry(row, j);
+                }
+                y[row] - q_gamma * smoothing
+            })
+            .collect();
+        let mut second_derivatives = Vec::with_capacity(n);
+        second_derivatives.push(S::zero());
+        second_derivatives.extend(gamma);
+        second_derivatives.push(S::zero());
+
+        CubicSpline {
+            times,
+            values,
+            second_derivatives,
+        }
+    }
+
+    pub fn start_time(&self) -> Seconds {
+        self.times[0]
+    }
+
+    pub fn end_time(&self) -> Seconds {
+        *self.times.last().unwrap()
+    }
+
+    /// Finds the interval containing `time`, returning its index and the time clamped to the spline
+    fn locate(&self, time: Seconds) -> (usize, Seconds) {
+        let time = time.max(self.start_time()).min(self.end_time());
+        let index = match self
+            .times
+            .binary_search_by(|t| t.partial_cmp(&time).unwrap())
+        {
+            Ok(index) => index,
+            Err(index) => index - 1,
+        };
+        (index.min(self.times.len().saturating_sub(2)), time)
+    }
+
+    pub fn pos_at_time(&self, time: Seconds) -> S {
+        if self.times.len() == 1 {
+            return self.values[0];
+        }
+        let (i, time) = self.locate(time);
+        let h = self.times[i + 1] - self.times[i];
+        let a = time - self.times[i];
+        let b = self.times[i + 1] - time;
+        (self.values[i] * b + self.values[i + 1] * a) / h
+            + (self.second_derivatives[i] * (b * b * b / h - h * b)
+                + self.second_derivatives[i + 1] * (a * a * a / h - h * a))
+                / 6.
+    }
+
+    /// The velocity at `time`. This is zero outside of the spline.
+    pub fn vel_at_time(&self, time: Seconds) -> S {
+        if self.times.len() == 1 || time < self.start_time() || time > self.end_time() {
+            return S::zero();
+        }
+        let (i, time) = self.locate(time);
+        let h = self.times[i + 1] - self.times[i];
+        let a = time - self.times[i];
+        let b = self.times[i + 1] - time;
+        (self.values[i + 1] - self.values[i]) / h
+            + (self.second_derivatives[i + 1] * (3. * a * a / h - h)
+                - self.second_derivatives[i] * (3. * b * b / h - h))
+                / 6.
+    }
+
+    /// The acceleration at `time`. This is zero outside of the spline.
+    pub fn accel_at_time(&self, time: Seconds) -> S {
+        if self.times.len() == 1 || time < self.start_time() || time > self.end_time() {
+            return S::zero();
+        }
+        let (i, time) = self.locate(time);
+        let h = self.times[i + 1] - self.times[i];
+        let a = time - self.times[i];
+        let b = self.times[i + 1] - time;
+        (self.second_derivatives[i] * b + self.second_derivatives[i + 1] * a) / h
+    }
+}
+
+/// A trajectory which can be queried at any time.
+/// Before the start and after the end of the trajectory it is stationary at its first and last positions.
 pub trait Trajectory<S: Vector> {
     fn pos_at_time(&self, time: Seconds) -> S;
+    fn vel_at_time(&self, time: Seconds) -> S;
+    fn accel_at_time(&self, time: Seconds) -> S;
     fn end_time(&self) -> Seconds;
+
+    /// The heading of a vehicle following the trajectory, for trajectories which have one
+    fn heading_at_time(&self, _time: Seconds) -> Option<Radians> {
+        None
+    }
 }
 
+/// A trajectory made of samples at a uniform resolution, starting at time 0.
+/// Positions are linearly interpolated between the samples.
+/// Velocities and accelerations are estimated at each sample using finite differences, and also linearly interpolated.
 pub trait UniformResolutionTrajectory<S: Vector>: Trajectory<S> + Index<usize, Output = S> {
     fn resolution(&self) -> Seconds;
+
+    /// Samples the trajectory at a new resolution. The end time is rounded down to a multiple of the new resolution.
+    fn resample(&self, resolution: Seconds) -> Self
+    where
+        Self: Sized;
+}
+
+/// Linearly interpolates between values given at each of `len` samples spaced `time_step` apart.
+/// Times outside of the samples take the value of the nearest sample.
+fn interpolate_samples<S: Vector, F: Fn(usize) -> S>(
+    time_step: Seconds,
+    len: usize,
+    time: Seconds,
+    value_at: F,
+) -> S {
+    if len == 0 {
+        return S::zero();
+    }
+    let sample_coord = (time / time_step).max(0.).min((len - 1) as f64);
+    let sample_left = sample_coord.floor() as usize;
+    let sample_right = (sample_left + 1).min(len - 1);
+    let a = sample_coord - sample_left as f64;
+    if a == 0. {
+        return value_at(sample_left);
+    }
+    value_at(sample_left) * (1. - a) + value_at(sample_right) * a
+}
+
+/// Whether `time` is within the samples, outside of which the trajectory is stationary
+fn within_samples(time_step: Seconds, len: usize, time: Seconds) -> bool {
+    len > 1 && time >= 0. && time <= time_step * (len - 1) as f64
+}
+
+/// Velocity at sample `index` using central differences, or one-sided differences at the ends
+fn sample_velocity<S: Vector, F: Fn(usize) -> S>(
+    time_step: Seconds,
+    len: usize,
+    index: usize,
+    pos_at: &F,
+) -> S {
+    if len < 2 {
+        return S::zero();
+    }
+    if index == 0 {
+        (pos_at(1) - pos_at(0)) / time_step
+    } else if index == len - 1 {
+        (pos_at(len - 1) - pos_at(len - 2)) / time_step
+    } else {
+        (pos_at(index + 1) - pos_at(index - 1)) / (2. * time_step)
+    }
+}
+
+/// Acceleration at sample `index` using second differences, taking the value of the next sample in at the ends
+fn sample_acceleration<S: Vector, F: Fn(usize) -> S>(
+    time_step: Seconds,
+    len: usize,
+    index: usize,
+    pos_at: &F,
+) -> S {
+    if len < 3 {
+        return S::zero();
+    }
+    let index = index.max(1).min(len - 2);
+    (pos_at(index + 1) - pos_at(index) * 2. + pos_at(index - 1)) / (time_step * time_step)
+}
+
+fn uniform_vel_at_time<S: Vector, F: Fn(usize) -> S>(
+    time_step: Seconds,
+    len: usize,
+    time: Seconds,
+    pos_at: F,
+) -> S {
+    if !within_samples(time_step, len, time) {
+        return S::zero();
+    }
+    interpolate_samples(time_step, len, time, |index| {
+        sample_velocity(time_step, len, index, &pos_at)
+    })
+}
+
+fn uniform_accel_at_time<S: Vector, F: Fn(usize) -> S>(
+    time_step: Seconds,
+    len: usize,
+    time: Seconds,
+    pos_at: F,
+) -> S {
+    if !within_samples(time_step, len, time) {
+        return S::zero();
+    }
+    interpolate_samples(time_step, len, time, |index| {
+        sample_acceleration(time_step, len, index, &pos_at)
+    })
+}
+
+/// The number of samples at `resolution` needed to cover `end_time`, rounding down
+fn num_samples(end_time: Seconds, resolution: Seconds) -> usize {
+    (end_time / resolution + 1e-9).floor() as usize + 1
 }
 
 impl<S: Vector> Trajectory<S> for NaiveTrajectory<S> {
     fn pos_at_time(&self, time: Seconds) -> S {
-        if self.1.is_empty() {
-            return S::zero();
-        }
-
-        // Do linear interpolation between samples
-        if time < 0. {
-            return self.1[0];
-        }
-        let end_time = self.end_time();
-        if time > end_time {
-            return *self.1.last().unwrap();
-        }
+        interpolate_samples(self.0, self.1.len(), time, |index| self.1[index])
+    }
 
-        let sample_coord = time / end_time;
-        let sample_left = sample_coord.floor() as usize;
-        let sample_right = sample_coord.ceil() as usize;
-        let time_left = sample_left as f64 * self.0;
-        let time_right = sample_right as f64 * self.0;
-        let time_span = time_right - time_left;
-        let val_left = self.1[sample_left];
-        let val_right = self.1[sample_right];
-        let a = (time - time_left) / time_span;
+    fn vel_at_time(&self, time: Seconds) -> S {
+        uniform_vel_at_time(self.0, self.1.len(), time, |index| self.1[index])
+    }
 
-        val_left * (1. - a) + val_right * a
+    fn accel_at_time(&self, time: Seconds) -> S {
+        uniform_accel_at_time(self.0, self.1.len(), time, |index| self.1[index])
     }
 
     fn end_time(&self) -> Seconds {
-        self.0 * ((self.1.len() - 1) as f64)
+        self.0 * (self.1.len().saturating_sub(1) as f64)
     }
 }
 
@@ -106,6 +449,16 @@ impl<S: Vector> UniformResolutionTrajectory<S> for NaiveTrajectory<S> {
     fn resolution(&self) -> Seconds {
         self.time_step()
     }
+
+    fn resample(&self, resolution: Seconds) -> Self {
+        if self.1.is_empty() {
+            return NaiveTrajectory(resolution, Vec::new());
+        }
+        let data = (0..num_samples(self.end_time(), resolution))
+            .map(|index| self.pos_at_time(index as f64 * resolution))
+            .collect();
+        NaiveTrajectory(resolution, data)
+    }
 }
 
 impl<S: Vector> Index<usize> for NaiveTrajectory<S> {
@@ -115,6 +468,7 @@ impl<S: Vector> Index<usize> for NaiveTrajectory<S> {
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct Heading2DTrajectory(Seconds, Vec<(Metres2D, Radians)>);
 
 impl Heading2DTrajectory {
@@ -168,19 +522,148 @@ impl Heading2DTrajectory {
     }
 }
 
+/// Headings are taken from the direction of motion, keeping the previous heading while stationary
+impl From<NaiveTrajectory<Metres2D>> for Heading2DTrajectory {
+    fn from(trajectory: NaiveTrajectory<Metres2D>) -> Self {
+        let NaiveTrajectory(time_step, positions) = trajectory;
+        let segment_headings: Vec<Option<Radians>> = positions
+            .windows(2)
+            .map(|pair| {
+                let displacement = pair[1] - pair[0];
+                if displacement.length() > 1e-9 {
+                    Some(displacement.y.atan2(displacement.x))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let mut heading = segment_headings
+            .iter()
+            .filter_map(|&heading| heading)
+            .next()
+            .unwrap_or(0.);
+        let mut data = Vec::with_capacity(positions.len());
+        for (index, &pos) in positions.iter().enumerate() {
+            if index > 0 {
+                heading = segment_headings[index - 1].unwrap_or(heading);
+            }
+            data.push((pos, heading));
+        }
+        Heading2DTrajectory(time_step, data)
+    }
+}
+
+impl Trajectory<Metres2D> for Heading2DTrajectory {
+    fn pos_at_time(&self, time: Seconds) -> Metres2D {
+        interpolate_samples(self.0, self.1.len(), time, |index| self.1[index].0)
+    }
+
+    fn vel_at_time(&self, time: Seconds) -> Metres2D {
+        uniform_vel_at_time(self.0, self.1.len(), time, |index| self.1[index].0)
+    }
+
+    fn accel_at_time(&self, time: Seconds) -> Metres2D {
+        uniform_accel_at_time(self.0, self.1.len(), time, |index| self.1[index].0)
+    }
+
+    fn end_time(&self) -> Seconds {
+        self.0 * (self.1.len().saturating_sub(1) as f64)
+    }
+
+    /// The heading of the segment being driven at `time`, which is stored with the sample at the end of the segment
+    fn heading_at_time(&self, time: Seconds) -> Option<Radians> {
+        if self.1.is_empty() {
+            return None;
+        }
+        let sample_coord = (time / self.0).max(0.).min((self.1.len() - 1) as f64);
+        // allow for rounding errors when the time is at a sample
+        let index = (sample_coord - 1e-9).ceil().max(0.) as usize;
+        Some(self.1[index].1)
+    }
+}
+
+impl UniformResolutionTrajectory<Metres2D> for Heading2DTrajectory {
+    fn resolution(&self) -> Seconds {
+        self.time_step()
+    }
+
+    fn resample(&self, resolution: Seconds) -> Self {
+        if self.1.is_empty() {
+            return Heading2DTrajectory(resolution, Vec::new());
+        }
+        let data = (0..num_samples(self.end_time(), resolution))
+            .map(|index| {
+                let time = index as f64 * resolution;
+                (self.pos_at_time(time), self.heading_at_time(time).unwrap())
+            })
+            .collect();
+        Heading2DTrajectory(resolution, data)
+    }
+}
+
+/// Indexes the positions of the samples. Use `data` to access the headings as well.
 impl Index<usize> for Heading2DTrajectory {
-    type Output = (Metres2D, Radians);
-    fn index(&self, index: usize) -> &(Metres2D, Radians) {
-        &self.1[index]
+    type Output = Metres2D;
+    fn index(&self, index: usize) -> &Metres2D {
+        &self.1[index].0
+    }
+}
+
+/// How the speed of each segment is chosen by the simple trajectory generators.
+/// Each segment is either stationary, at full speed, or at a normally distributed random speed clamped to the maximum speed.
+#[derive(Debug, Clone, Copy)]
+pub struct SpeedDistribution {
+    /// Probability that a segment is stationary
+    pub p_stationary: f64,
+    /// Probability that a segment is at full speed
+    pub p_full_speed: f64,
+    /// Probability that a full-speed segment goes forwards rather than backwards
+    pub p_forward: f64,
+    /// Mean of the random speeds, as a fraction of the maximum speed
+    pub random_speed_mean: f64,
+    /// Standard deviation of the random speeds, as a fraction of the maximum speed
+    pub random_speed_sd: f64,
+}
+
+impl SpeedDistribution {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        let is_probability = |p: f64| p >= 0. && p <= 1.;
+        if !is_probability(self.p_stationary)
+            || !is_probability(self.p_full_speed)
+            || !is_probability(self.p_forward)
+        {
+            return Err("speed probabilities must be between 0 and 1");
+        }
+        if self.p_stationary + self.p_full_speed > 1. {
+            return Err("stationary and full-speed probabilities must not add up to more than 1");
+        }
+        if self.random_speed_sd.is_nan()
+            || self.random_speed_sd < 0.
+            || !self.random_speed_mean.is_finite()
+        {
+            return Err("random speed distribution is invalid");
+        }
+        Ok(())
+    }
+}
+
+impl Default for SpeedDistribution {
+    fn default() -> Self {
+        // 95% of random speeds lie between -max_speed and max_speed
+        SpeedDistribution {
+            p_stationary: 0.2,
+            p_full_speed: 0.5,
+            p_forward: 0.85,
+            random_speed_mean: 0.,
+            random_speed_sd: 1. / 1.96,
+        }
     }
 }
 
-/// A simple, non-configurable trajectory generation algorithm.
+/// A simple trajectory generation algorithm.
 /// `variability` controls how many segments the trajectory has – the segments generated have a mean length of `min_length / variability`.
 /// `rsd` controls how much the segment length varies.
-///
-/// The algorithm hardcodes a probability of 1/4 stationary, 1/4 full-speed with 3/4 chance forward, and 1/2 randomly-chosen speed.
-/// This could be made configurable in the future.
+/// `speeds` controls how the speed of each segment is chosen.
 ///
 /// The trajectory always starts at the origin.
 pub fn generate_1d_trajectory_points_simple(
@@ -188,6 +671,7 @@ pub fn generate_1d_trajectory_points_simple(
     min_length: Seconds,
     variability: f64,
     rsd: f64,
+    speeds: &SpeedDistribution,
 ) -> Vec<(Seconds, Metres)> {
     let mut rng = SmallRng::from_rng(thread_rng()).unwrap();
     let segment_length_dist = {
@@ -195,7 +679,7 @@ pub fn generate_1d_trajectory_points_simple(
         let sd = mean * rsd;
         Normal::new(mean, sd)
     };
-    let generator = SpeedGenerator::new(max_speed, 0.2, 0.7, 0.85);
+    let generator = SpeedGenerator::new(max_speed, speeds);
     let mut cur_time = 0.;
     let mut cur_pos = 0.;
     let mut points = vec![(cur_time, cur_pos)];
@@ -221,6 +705,7 @@ pub fn generate_2d_trajectory_points_simple(
     variability: f64,
     rsd: f64,
     turnability: f64,
+    speeds: &SpeedDistribution,
 ) -> Vec<(Seconds, Metres2D)> {
     let mut rng = SmallRng::from_rng(thread_rng()).unwrap();
     let segment_length_dist = {
@@ -229,7 +714,7 @@ pub fn generate_2d_trajectory_points_simple(
         Normal::new(mean, sd)
     };
     let heading_dist = Normal::new(0., turnability);
-    let speed_generator = SpeedGenerator::new(max_speed, 0.2, 0.7, 0.85);
+    let speed_generator = SpeedGenerator::new(max_speed, speeds);
     let mut cur_time = 0.;
     let mut cur_pos = Metres2D::zero();
     let mut cur_heading = {
@@ -264,6 +749,7 @@ pub fn generate_2d_trajectory_points_with_heading(
     variability: f64,
     rsd: f64,
     turnability: f64,
+    speeds: &SpeedDistribution,
 ) -> Vec<(Seconds, Metres2D, Radians)> {
     let mut rng = SmallRng::from_rng(thread_rng()).unwrap();
     let segment_length_dist = {
@@ -272,7 +758,7 @@ pub fn generate_2d_trajectory_points_with_heading(
         Normal::new(mean, sd)
     };
     let heading_dist = Normal::new(0., turnability);
-    let speed_generator = SpeedGenerator::new(max_speed, 0.2, 0.7, 0.85);
+    let speed_generator = SpeedGenerator::new(max_speed, speeds);
     let mut cur_time = 0.;
     let mut cur_pos = Metres2D::zero();
     let mut cur_heading = {
@@ -300,6 +786,58 @@ pub fn generate_2d_trajectory_points_with_heading(
     points
 }
 
+/// A version of `generate_2d_trajectory_points_simple` adapted for 3D.
+/// Each segment also climbs or descends at a rate drawn uniformly from ±`max_climb_rate`,
+/// with the whole velocity scaled down if it would exceed the maximum speed.
+pub fn generate_3d_trajectory_points_simple(
+    max_speed: MetresPerSecond,
+    min_length: Seconds,
+    variability: f64,
+    rsd: f64,
+    turnability: f64,
+    max_climb_rate: MetresPerSecond,
+    speeds: &SpeedDistribution,
+) -> Vec<(Seconds, Metres3D)> {
+    let mut rng = SmallRng::from_rng(thread_rng()).unwrap();
+    let segment_length_dist = {
+        let mean = min_length / variability;
+        let sd = mean * rsd;
+        Normal::new(mean, sd)
+    };
+    let heading_dist = Normal::new(0., turnability);
+    let climb_rate_dist = Uniform::new_inclusive(-max_climb_rate, max_climb_rate);
+    let speed_generator = SpeedGenerator::new(max_speed, speeds);
+    let mut cur_time = 0.;
+    let mut cur_pos = Metres3D::zero();
+    let mut cur_heading = {
+        let sample: f64 = rng.sample(Standard);
+        (sample * 2. * PI) - PI
+    };
+    let mut points = vec![(cur_time, cur_pos)];
+
+    while cur_time < min_length {
+        let seg_length = segment_length_dist.sample(&mut rng);
+        if seg_length <= 0. {
+            continue;
+        }
+        cur_time += seg_length;
+        let speed = speed_generator.gen_speed(&mut rng);
+        cur_heading += heading_dist.sample(&mut rng);
+        let horizontal = PolarMetres2D {
+            r: speed,
+            theta: cur_heading,
+        }.to_cartesian();
+        let mut velocity = Metres3D::from_horizontal(horizontal, climb_rate_dist.sample(&mut rng));
+        if velocity.length() > max_speed {
+            velocity *= max_speed / velocity.length();
+        }
+        cur_pos += velocity * seg_length;
+        points.push((cur_time, cur_pos));
+    }
+
+    points
+}
+
 struct SpeedGenerator {
     max_speed: MetresPerSecond,
     thresh_fullspeed: f64,
@@ -309,20 +847,16 @@ struct SpeedGenerator {
 }
 
 impl SpeedGenerator {
-    fn new(
-        max_speed: MetresPerSecond,
-        thresh_fullspeed: f64,
-        thresh_randomspeed: f64,
-        thresh_forward: f64,
-    ) -> Self {
-        let speed_sd = max_speed / 1.96;
-        // 95% of returned values will lie between -max_speed and max_speed
-        let speed_dist = Normal::new(0., speed_sd);
+    fn new(max_speed: MetresPerSecond, speeds: &SpeedDistribution) -> Self {
+        let speed_dist = Normal::new(
+            speeds.random_speed_mean * max_speed,
+            speeds.random_speed_sd * max_speed,
+        );
         SpeedGenerator {
             max_speed,
-            thresh_fullspeed,
-            thresh_randomspeed,
-            thresh_forward,
+            thresh_fullspeed: speeds.p_stationary,
+            thresh_randomspeed: speeds.p_stationary + speeds.p_full_speed,
+            thresh_forward: speeds.p_forward,
             speed_dist,
         }
     }
@@ -349,3 +883,788 @@ impl SpeedGenerator {
         return self.clamp_speed(self.speed_dist.sample(rng));
     }
 }
+
+/// A mean-reverting Ornstein–Uhlenbeck process.
+/// `sd` is the standard deviation of the stationary distribution, and `time_constant` is the time over which
+/// the correlation between values falls by a factor of e.
+#[derive(Debug, Clone, Copy)]
+pub struct OrnsteinUhlenbeck {
+    pub mean: f64,
+    pub sd: f64,
+    pub time_constant: Seconds,
+}
+
+impl OrnsteinUhlenbeck {
+    pub fn validate(&self) -> Result<(), &'static str> {
+        if !self.mean.is_finite() || !self.sd.is_finite() || self.sd < 0. {
+            return Err("process mean and standard deviation must be finite, and the deviation non-negative");
+        }
+        if self.time_constant.is_nan() || self.time_constant <= 0. {
+            return Err("process time constant must be positive");
+        }
+        Ok(())
+    }
+
+    /// Samples the stationary distribution
+    fn initial<R: Rng + ?Sized>(&self, rng: &mut R) -> f64 {
+        Normal::new(self.mean, self.sd).sample(rng)
+    }
+
+    /// Advances `value` by `dt`, using the exact transition distribution of the process
+    fn step<R: Rng + ?Sized>(&self, value: f64, dt: Seconds, rng: &mut R) -> f64 {
+        let decay = (-dt / self.time_constant).exp();
+        let sd = self.sd * (1. - decay.powi(2)).sqrt();
+        self.mean + (value - self.mean) * decay + Normal::new(0., sd).sample(rng)
+    }
+}
+
+/// A trajectory whose velocity follows an Ornstein–Uhlenbeck process, sampled every `resolution`.
+/// The velocity is clamped to the maximum speed.
+///
+/// The trajectory always starts at the origin.
+pub fn generate_1d_trajectory_points_ou(
+    max_speed: MetresPerSecond,
+    min_length: Seconds,
+    resolution: Seconds,
+    velocity: &OrnsteinUhlenbeck,
+) -> Vec<(Seconds, Metres)> {
+    let mut rng = SmallRng::from_rng(thread_rng()).unwrap();
+    let clamp = |v: f64| v.max(-max_speed).min(max_speed);
+    let mut cur_time = 0.;
+    let mut cur_pos = 0.;
+    let mut cur_velocity = velocity.initial(&mut rng);
+    let mut points = vec![(cur_time, cur_pos)];
+
+    while cur_time < min_length {
+        cur_time = points.len() as f64 * resolution;
+        cur_pos += clamp(cur_velocity) * resolution;
+        cur_velocity = velocity.step(cur_velocity, resolution, &mut rng);
+        points.push((cur_time, cur_pos));
+    }
+
+    points
+}
+
+/// A version of `generate_1d_trajectory_points_ou` adapted for 2D, with each component of the velocity an independent process.
+/// The speed is clamped to the maximum speed.
+pub fn generate_2d_trajectory_points_ou(
+    max_speed: MetresPerSecond,
+    min_length: Seconds,
+    resolution: Seconds,
+    velocity: &OrnsteinUhlenbeck,
+) -> Vec<(Seconds, Metres2D)> {
+    let mut rng = SmallRng::from_rng(thread_rng()).unwrap();
+    let mut cur_time = 0.;
+    let mut cur_pos = Metres2D::zero();
+    let mut cur_velocity = Metres2D::new(velocity.initial(&mut rng), velocity.initial(&mut rng));
+    let mut points = vec![(cur_time, cur_pos)];
+
+    while cur_time < min_length {
+        cur_time = points.len() as f64 * resolution;
+        let speed = cur_velocity.length();
+        let clamped = if speed > max_speed {
+            cur_velocity * (max_speed / speed)
+        } else {
+            cur_velocity
+        };
+        cur_pos += clamped * resolution;
+        cur_velocity = Metres2D::new(
+            velocity.step(cur_velocity.x, resolution, &mut rng),
+            velocity.step(cur_velocity.y, resolution, &mut rng),
+        );
+        points.push((cur_time, cur_pos));
+    }
+
+    points
+}
+
+/// A correlated random walk, sampled every `resolution`.
+/// Both the speed and the turn rate (in radians per second) follow Ornstein–Uhlenbeck processes,
+/// and the speed is clamped between 0 and the maximum speed.
+/// Each point also has the heading of the _previous_ step in radians, the first having the random initial heading.
+///
+/// The trajectory always starts at the origin.
+pub fn generate_2d_trajectory_points_crw(
+    max_speed: MetresPerSecond,
+    min_length: Seconds,
+    resolution: Seconds,
+    speed: &OrnsteinUhlenbeck,
+    turn_rate: &OrnsteinUhlenbeck,
+) -> Vec<(Seconds, Metres2D, Radians)> {
+    let mut rng = SmallRng::from_rng(thread_rng()).unwrap();
+    let mut cur_time = 0.;
+    let mut cur_pos = Metres2D::zero();
+    let mut cur_heading = {
+        let sample: f64 = rng.sample(Standard);
+        (sample * 2. * PI) - PI
+    };
+    let mut cur_speed = speed.initial(&mut rng);
+    let mut cur_turn_rate = turn_rate.initial(&mut rng);
+    let mut points = vec![(cur_time, cur_pos, cur_heading)];
+
+    while cur_time < min_length {
+        cur_time = points.len() as f64 * resolution;
+        cur_heading += cur_turn_rate * resolution;
+        let velocity = PolarMetres2D {
+            r: cur_speed.max(0.).min(max_speed),
+            theta: cur_heading,
+        }.to_cartesian();
+        cur_pos += velocity * resolution;
+        cur_speed = speed.step(cur_speed, resolution, &mut rng);
+        cur_turn_rate = turn_rate.step(cur_turn_rate, resolution, &mut rng);
+        points.push((cur_time, cur_pos, cur_heading));
+    }
+
+    points
+}
+
+/// Limits on how a reference trajectory may move. Limits which are None are not enforced.
+/// The turn rate limit only applies with more than one dimension.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KinematicLimits {
+    pub max_accel: Option<f64>,
+    pub max_jerk: Option<f64>,
+    pub max_turn_rate: Option<RadiansPerSecond>,
+}
+
+/// The peak speed, acceleration, jerk and turn rate of a trajectory, from finite differences of its samples.
+/// Trajectories are taken to start at rest.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FeasibilityReport {
+    pub peak_speed: MetresPerSecond,
+    pub peak_accel: f64,
+    pub peak_jerk: f64,
+    pub peak_turn_rate: RadiansPerSecond,
+}
+
+impl FeasibilityReport {
+    pub fn new<S: Vector>(trajectory: &NaiveTrajectory<S>) -> Self {
+        let dt = trajectory.time_step();
+        let differences = |values: &[S]| -> Vec<S> {
+            let rates = values.windows(2).map(|pair| (pair[1] - pair[0]) / dt);
+            Some(S::zero()).into_iter().chain(rates).collect()
+        };
+        let peak = |values: &[S]| values.iter().map(|v| v.length().abs()).fold(0., f64::max);
+        let velocities = differences(trajectory.data());
+        let accelerations = differences(&velocities);
+        let jerks = differences(&accelerations);
+        let peak_turn_rate = if S::repr_length() > 1 {
+            velocities
+                .windows(2)
+                .filter_map(|pair| turn_angle(pair[0], pair[1]))
+                .fold(0., f64::max)
+                / dt
+        } else {
+            0.
+        };
+        FeasibilityReport {
+            peak_speed: peak(&velocities),
+            peak_accel: peak(&accelerations),
+            peak_jerk: peak(&jerks),
+            peak_turn_rate,
+        }
+    }
+
+    pub fn is_feasible(&self, max_speed: MetresPerSecond, limits: &KinematicLimits) -> bool {
+        let within = |value: f64, limit: Option<f64>| {
+            limit.map_or(true, |limit| value <= limit * (1. + 1e-9) + 1e-9)
+        };
+        within(self.peak_speed, Some(max_speed))
+            && within(self.peak_accel, limits.max_accel)
+            && within(self.peak_jerk, limits.max_jerk)
+            && within(self.peak_turn_rate, limits.max_turn_rate)
+    }
+}
+
+/// Angle between the directions of two velocities, or None if either is stationary
+fn turn_angle<S: Vector>(from: S, to: S) -> Option<Radians> {
+    let (from_speed, to_speed) = (from.length(), to.length());
+    if from_speed > 1e-9 && to_speed > 1e-9 {
+        let cos = from.dot(&to) / (from_speed * to_speed);
+        Some(cos.max(-1.).min(1.).acos())
+    } else {
+        None
+    }
+}
+
+/// Filters a trajectory so that it respects the limits, by following its velocity as closely as they allow.
+/// The filtered trajectory can drift away from the original one where the original is infeasible.
+///
+/// Speed and turn rate limits take priority, so the acceleration and jerk limits may be exceeded where they conflict.
+/// Turning in place is allowed, so a trajectory may reverse by coming to a stop.
+pub fn limit_kinematics<S: Vector>(
+    trajectory: &NaiveTrajectory<S>,
+    max_speed: MetresPerSecond,
+    limits: &KinematicLimits,
+) -> NaiveTrajectory<S> {
+    let dt = trajectory.time_step();
+    let data = trajectory.data();
+    let clamp_length = |value: S, max: f64| {
+        // the length of a 1D vector is signed
+        let length = value.length().abs();
+        if length > max {
+            value * (max / length)
+        } else {
+            value
+        }
+    };
+    let mut pos = data[0];
+    let mut velocity = S::zero();
+    let mut accel = S::zero();
+    let mut points = Vec::with_capacity(data.len());
+    points.push(pos);
+    for pair in data.windows(2) {
+        let target = clamp_length((pair[1] - pair[0]) / dt, max_speed);
+        let error = target - velocity;
+        let mut desired_accel = error / dt;
+        if let Some(max_jerk) = limits.max_jerk {
+            // slow down the approach so that the acceleration can ramp back to zero without overshooting
+            let max_approach = (2. * max_jerk * error.length().abs()).sqrt();
+            desired_accel = clamp_length(desired_accel, max_approach);
+        }
+        if let Some(max_accel) = limits.max_accel {
+            desired_accel = clamp_length(desired_accel, max_accel);
+        }
+        if let Some(max_jerk) = limits.max_jerk {
+            desired_accel = accel + clamp_length(desired_accel - accel, max_jerk * dt);
+        }
+        let mut new_velocity = clamp_length(velocity + desired_accel * dt, max_speed);
+        if let (Some(max_turn_rate), true) = (limits.max_turn_rate, S::repr_length() > 1) {
+            new_velocity = limit_turn(velocity, new_velocity, max_turn_rate * dt);
+        }
+        accel = (new_velocity - velocity) / dt;
+        velocity = new_velocity;
+        pos += velocity * dt;
+        points.push(pos);
+    }
+    NaiveTrajectory(dt, points)
+}
+
+/// Rotates `to` towards the direction of `from` so that it turns by at most `max_angle`, keeping its speed.
+/// Reversals which cannot be rotated come to a stop instead.
+fn limit_turn<S: Vector>(from: S, to: S, max_angle: Radians) -> S {
+    match turn_angle(from, to) {
+        Some(angle) if angle > max_angle => {
+            let speed = to.length();
+            let along = from / from.length();
+            let across = to / speed - along * along.dot(&(to / speed));
+            let across_length = across.length();
+            if across_length > 1e-9 {
+                (along * max_angle.cos() + across * (max_angle.sin() / across_length)) * speed
+            } else {
+                S::zero()
+            }
+        }
+        _ => to,
+    }
+}
+
+/// Deterministic parametric motions, repeating every `period`.
+/// `phase` is the position in the cycle at the start, so `2π` is a whole period.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// Oscillation along the x axis with the given `amplitude`
+    Sinusoid,
+    /// Anticlockwise circle with the given `radius`
+    Circle,
+    /// Figure-eight with half-width `radius`
+    FigureEight,
+    /// Lissajous curve of the given `amplitude`, whose y frequency is `ratio` times its x frequency
+    Lissajous,
+    /// Square of half-side `amplitude` with corners rounded to `radius`, traversed at constant speed
+    RoundedSquare,
+    /// Moves `amplitude` along the x axis at constant speed in the first half of each period,
+    /// then stops for the second half
+    StopAndGo,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PatternParams {
+    pub period: Seconds,
+    pub amplitude: Metres,
+    pub phase: Radians,
+    pub radius: Metres,
+    pub ratio: f64,
+}
+
+impl Default for PatternParams {
+    fn default() -> Self {
+        PatternParams {
+            period: 10.,
+            amplitude: 1.,
+            phase: 0.,
+            radius: 1.,
+            ratio: 3.,
+        }
+    }
+}
+
+impl Pattern {
+    pub fn from_name(name: &str) -> Option<Pattern> {
+        match name {
+            "sinusoid" => Some(Pattern::Sinusoid),
+            "circle" => Some(Pattern::Circle),
+            "figure_eight" => Some(Pattern::FigureEight),
+            "lissajous" => Some(Pattern::Lissajous),
+            "rounded_square" => Some(Pattern::RoundedSquare),
+            "stop_and_go" => Some(Pattern::StopAndGo),
+            _ => None,
+        }
+    }
+
+    pub fn names() -> &'static [&'static str] {
+        &[
+            "sinusoid",
+            "circle",
+            "figure_eight",
+            "lissajous",
+            "rounded_square",
+            "stop_and_go",
+        ]
+    }
+
+    /// The names of the patterns which can be used in 1D
+    pub fn names_1d() -> Vec<&'static str> {
+        Pattern::names()
+            .iter()
+            .cloned()
+            .filter(|name| Pattern::from_name(name).filter(Pattern::is_1d).is_some())
+            .collect()
+    }
+
+    /// Whether the pattern only moves along the x axis, so can be used in 1D
+    pub fn is_1d(&self) -> bool {
+        match *self {
+            Pattern::Sinusoid | Pattern::StopAndGo => true,
+            _ => false,
+        }
+    }
+
+    pub fn validate(&self, params: &PatternParams) -> Result<(), &'static str> {
+        if !params.period.is_finite() || params.period <= 0. {
+            return Err("pattern period must be positive");
+        }
+        let is_length = |l: f64| l >= 0. && l.is_finite();
+        if !is_length(params.amplitude) || !is_length(params.radius) || !params.phase.is_finite() {
+            return Err("pattern amplitude and radius must be non-negative, and phase finite");
+        }
+        if *self == Pattern::Lissajous && (params.ratio.is_nan() || params.ratio <= 0.) {
+            return Err("lissajous frequency ratio must be positive");
+        }
+        if *self == Pattern::RoundedSquare && params.radius > params.amplitude {
+            return Err("rounded square corner radius must not be more than its half-side");
+        }
+        Ok(())
+    }
+
+    /// The position at cycle angle `theta`, before moving the start to the origin
+    fn position(&self, params: &PatternParams, theta: Radians) -> Metres2D {
+        let (a, r) = (params.amplitude, params.radius);
+        match *self {
+            Pattern::Sinusoid => Metres2D::new(a * theta.sin(), 0.),
+            Pattern::Circle => Metres2D::new(r * theta.cos(), r * theta.sin()),
+            Pattern::FigureEight => Metres2D::new(r * theta.sin(), r * theta.sin() * theta.cos()),
+            Pattern::Lissajous => Metres2D::new(a * theta.cos(), a * (params.ratio * theta).sin()),
+            Pattern::RoundedSquare => {
+                let cycle = theta / (2. * PI);
+                rounded_square_position(a, r, cycle - cycle.floor())
+            }
+            Pattern::StopAndGo => {
+                let cycle = theta / (2. * PI);
+                let moving = (2. * (cycle - cycle.floor())).min(1.);
+                Metres2D::new(a * (cycle.floor() + moving), 0.)
+            }
+        }
+    }
+}
+
+/// Position a `fraction` of the way round a rounded square, starting at the beginning of its bottom side
+fn rounded_square_position(half_side: Metres, radius: Metres, fraction: f64) -> Metres2D {
+    let side = 2. * (half_side - radius);
+    let arc = radius * PI / 2.;
+    let mut remaining = fraction * 4. * (side + arc);
+    let mut pos = Metres2D::new(-side / 2., -half_side);
+    let mut heading: Radians = 0.;
+    for _ in 0..4 {
+        if remaining <= side {
+            return pos + PolarMetres2D::new(remaining, heading).to_cartesian();
+        }
+        pos += PolarMetres2D::new(side, heading).to_cartesian();
+        remaining -= side;
+        let centre = pos + PolarMetres2D::new(radius, heading + PI / 2.).to_cartesian();
+        if remaining <= arc {
+            let angle = heading - PI / 2. + remaining / radius;
+            return centre + PolarMetres2D::new(radius, angle).to_cartesian();
+        }
+        pos = centre + PolarMetres2D::new(radius, heading).to_cartesian();
+        remaining -= arc;
+        heading += PI / 2.;
+    }
+    pos
+}
+
+/// Samples a pattern every `resolution`.
+///
+/// The trajectory always starts at the origin.
+pub fn generate_2d_trajectory_points_pattern(
+    pattern: Pattern,
+    params: &PatternParams,
+    min_length: Seconds,
+    resolution: Seconds,
+) -> Vec<(Seconds, Metres2D)> {
+    let theta = |time: Seconds| 2. * PI * time / params.period + params.phase;
+    let start = pattern.position(params, theta(0.));
+    let num_points = (min_length / resolution).ceil() as usize + 1;
+    (0..num_points)
+        .map(|i| {
+            let time = i as f64 * resolution;
+            (time, pattern.position(params, theta(time)) - start)
+        }).collect()
+}
+
+/// A version of `generate_2d_trajectory_points_pattern` for the patterns which can be used in 1D.
+pub fn generate_1d_trajectory_points_pattern(
+    pattern: Pattern,
+    params: &PatternParams,
+    min_length: Seconds,
+    resolution: Seconds,
+) -> Result<Vec<(Seconds, Metres)>, &'static str> {
+    if !pattern.is_1d() {
+        return Err("the pattern is only supported in 2 dimensions");
+    }
+    Ok(
+        generate_2d_trajectory_points_pattern(pattern, params, min_length, resolution)
+            .into_iter()
+            .map(|(t, pos)| (t, pos.x))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speed_distribution() {
+        let stationary = SpeedDistribution {
+            p_stationary: 1.,
+            p_full_speed: 0.,
+            ..Default::default()
+        };
+        let points = generate_1d_trajectory_points_simple(1., 20., 10., 0.2, &stationary);
+        assert!(points.iter().all(|&(_, x)| x == 0.));
+
+        let forwards = SpeedDistribution {
+            p_stationary: 0.,
+            p_full_speed: 1.,
+            p_forward: 1.,
+            ..Default::default()
+        };
+        let points = generate_1d_trajectory_points_simple(1., 20., 10., 0.2, &forwards);
+        assert!(points.iter().all(|&(t, x)| (x - t).abs() < 1e-9));
+
+        assert!(SpeedDistribution::default().validate().is_ok());
+        let invalid = SpeedDistribution {
+            p_stationary: 0.6,
+            p_full_speed: 0.6,
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn interpolating_spline() {
+        let points = [(0., 0.), (1., 1.), (2.5, -1.), (3., 0.5), (5., 2.)];
+        let spline = CubicSpline::new(&points, 0.);
+        for &(t, x) in points.iter() {
+            assert!((spline.pos_at_time(t) - x).abs() < 1e-9);
+        }
+        // natural end conditions
+        assert!(spline.accel_at_time(0.).abs() < 1e-9);
+        assert!(spline.accel_at_time(5.).abs() < 1e-9);
+        // velocity and acceleration are continuous at the knots
+        for &(t, _) in points[1..4].iter() {
+            let eps = 1e-7;
+            assert!((spline.vel_at_time(t - eps) - spline.vel_at_time(t + eps)).abs() < 1e-5);
+            assert!((spline.accel_at_time(t - eps) - spline.accel_at_time(t + eps)).abs() < 1e-5);
+        }
+        // and velocity is the derivative of position
+        let h = 1e-6;
+        let numeric = (spline.pos_at_time(1.7 + h) - spline.pos_at_time(1.7 - h)) / (2. * h);
+        assert!((numeric - spline.vel_at_time(1.7)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn smoothing_spline() {
+        let points: Vec<(Seconds, Metres2D)> = (0..10)
+            .map(|i| {
+                let t = i as f64;
+                let sign = if i % 2 == 0 { 1. } else { -1. };
+                (t, Metres2D { x: t, y: sign })
+            })
+            .collect();
+        let wiggle = |spline: &CubicSpline<Metres2D>| {
+            (0..90)
+                .map(|i| spline.accel_at_time(i as f64 * 0.1).length())
+                .sum::<f64>()
+        };
+        let rough = CubicSpline::new(&points, 0.);
+        let smooth = CubicSpline::new(&points, 10.);
+        assert!(wiggle(&smooth) < wiggle(&rough));
+        // a straight line is unaffected by smoothing
+        let line = CubicSpline::new(&[(0., 0.), (1., 2.), (3., 6.), (4., 8.)], 10.);
+        assert!((line.pos_at_time(2.) - 4.).abs() < 1e-9);
+
+        let trajectory = NaiveTrajectory::from_spline(0.1, &points, 1.);
+        assert_eq!(trajectory.data().len(), 91);
+    }
+
+    #[test]
+    fn crop_irregular_points() {
+        let points = [(0.5, 1.), (0.7, 2.), (1.6, 0.), (3., 7.)];
+        let cropped = crop_points(&points, Some(1.), Some(2.3), true);
+        assert_eq!(cropped.len(), 3);
+        assert!(cropped[0].0 == 0. && cropped[0].1 == 0.);
+        assert!((cropped[1].0 - 0.6).abs() < 1e-9);
+        assert!((cropped[1].1 - (0. - 4. / 3.)).abs() < 1e-9);
+        assert!((cropped[2].0 - 1.3).abs() < 1e-9);
+        assert!((cropped[2].1 - (3.5 - 4. / 3.)).abs() < 1e-9);
+
+        let uncropped = crop_points(&points, None, None, false);
+        assert_eq!(uncropped.len(), 4);
+        assert!(uncropped[3].0 == 2.5 && uncropped[3].1 == 7.);
+
+        let trajectory = NaiveTrajectory::from_points(0.5, uncropped);
+        assert_eq!(trajectory.data().len(), 6);
+        assert!((trajectory[1] - 4. / 3.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn naive_trajectory_queries() {
+        // x = t^2 sampled every 0.5s up to 2s
+        let trajectory = NaiveTrajectory(0.5, vec![0., 0.25, 1., 2.25, 4.]);
+        assert_eq!(trajectory.end_time(), 2.);
+        assert!((trajectory.pos_at_time(0.75) - 0.625).abs() < 1e-9);
+        assert!((trajectory.pos_at_time(1.6) - (2.25 + 0.2 * 1.75)).abs() < 1e-9);
+        assert_eq!(trajectory.pos_at_time(-1.), 0.);
+        assert_eq!(trajectory.pos_at_time(3.), 4.);
+
+        // central differences are exact for a quadratic
+        assert!((trajectory.vel_at_time(1.) - 2.).abs() < 1e-9);
+        assert!((trajectory.vel_at_time(1.25) - 2.5).abs() < 1e-9);
+        assert!((trajectory.accel_at_time(0.8) - 2.).abs() < 1e-9);
+        assert_eq!(trajectory.vel_at_time(2.5), 0.);
+        assert_eq!(trajectory.accel_at_time(-0.5), 0.);
+
+        let resampled = trajectory.resample(0.4);
+        assert_eq!(resampled.resolution(), 0.4);
+        assert_eq!(resampled.data().len(), 6);
+        assert!((resampled[2] - trajectory.pos_at_time(0.8)).abs() < 1e-9);
+        assert!((resampled[5] - 4.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn heading_trajectory_queries() {
+        let points = vec![
+            (0., Metres2D::zero(), 0.),
+            (1., Metres2D { x: 1., y: 0. }, 0.),
+            (2., Metres2D { x: 1., y: 1. }, PI / 2.),
+        ];
+        let trajectory = Heading2DTrajectory::from_points(0.25, points);
+        assert_eq!(trajectory.end_time(), 2.);
+        assert_eq!(trajectory[4], Metres2D { x: 1., y: 0. });
+        let pos = trajectory.pos_at_time(1.6);
+        assert!((pos.x - 1.).abs() < 1e-9 && (pos.y - 0.6).abs() < 1e-9);
+        let vel = trajectory.vel_at_time(0.5);
+        assert!((vel.x - 1.).abs() < 1e-9 && vel.y.abs() < 1e-9);
+        assert!(trajectory.accel_at_time(1.).length() > 0.);
+        assert!(trajectory.accel_at_time(0.5).x.abs() < 1e-9);
+        assert_eq!(trajectory.heading_at_time(0.9), Some(0.));
+        assert_eq!(trajectory.heading_at_time(1.), Some(0.));
+        assert_eq!(trajectory.heading_at_time(1.1), Some(PI / 2.));
+
+        let resampled = trajectory.resample(0.5);
+        assert_eq!(resampled.data().len(), 5);
+        assert_eq!(resampled.data()[3], (Metres2D { x: 1., y: 0.5 }, PI / 2.));
+
+        let naive = NaiveTrajectory(
+            1.,
+            vec![
+                Metres2D::zero(),
+                Metres2D::zero(),
+                Metres2D { x: 0., y: 1. },
+                Metres2D { x: 0., y: 1. },
+                Metres2D { x: -1., y: 1. },
+            ],
+        );
+        let from_naive = Heading2DTrajectory::from(naive);
+        let headings: Vec<Radians> = from_naive.data().iter().map(|&(_, h)| h).collect();
+        assert_eq!(headings, vec![PI / 2., PI / 2., PI / 2., PI / 2., PI]);
+        assert_eq!(from_naive.heading_at_time(-1.), Some(PI / 2.));
+        assert_eq!(NaiveTrajectory(1., vec![0., 1.]).heading_at_time(0.5), None);
+    }
+
+    #[test]
+    fn stochastic_process_trajectories() {
+        let velocity = OrnsteinUhlenbeck {
+            mean: 0.,
+            sd: 1.,
+            time_constant: 2.,
+        };
+        assert!(velocity.validate().is_ok());
+        let instantaneous = OrnsteinUhlenbeck {
+            time_constant: 0.,
+            ..velocity
+        };
+        assert!(instantaneous.validate().is_err());
+        // with no noise the process decays exponentially towards the mean
+        let decaying = OrnsteinUhlenbeck { sd: 0., ..velocity };
+        let mut rng = SmallRng::from_rng(thread_rng()).unwrap();
+        assert!((decaying.step(1., 2., &mut rng) - (-1f64).exp()).abs() < 1e-12);
+
+        let points = generate_1d_trajectory_points_ou(0.5, 10., 0.1, &velocity);
+        assert_eq!(points.len(), 101);
+        for (i, pair) in points.windows(2).enumerate() {
+            assert_eq!(pair[1].0, (i + 1) as f64 * 0.1);
+            assert!((pair[1].1 - pair[0].1).abs() <= 0.05 + 1e-12);
+        }
+
+        let points = generate_2d_trajectory_points_ou(0.5, 10., 0.1, &velocity);
+        assert_eq!(points.len(), 101);
+        for pair in points.windows(2) {
+            assert!((pair[1].1 - pair[0].1).length() <= 0.05 + 1e-12);
+        }
+
+        let speed = OrnsteinUhlenbeck {
+            mean: 0.3,
+            sd: 0.2,
+            time_constant: 1.,
+        };
+        let points = generate_2d_trajectory_points_crw(0.5, 10., 0.1, &speed, &velocity);
+        assert_eq!(points.len(), 101);
+        for pair in points.windows(2) {
+            let (_, from, _) = pair[0];
+            let (_, to, heading) = pair[1];
+            let step = to - from;
+            assert!(step.length() <= 0.05 + 1e-12);
+            // every step is in the direction of the heading
+            let along = step.x * heading.cos() + step.y * heading.sin();
+            assert!((along - step.length()).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn patterns() {
+        let params = PatternParams {
+            period: 4.,
+            amplitude: 2.,
+            phase: PI / 2.,
+            radius: 1.,
+            ratio: 3.,
+        };
+        assert_eq!(Pattern::names_1d(), vec!["sinusoid", "stop_and_go"]);
+        for name in Pattern::names() {
+            let pattern = Pattern::from_name(name).unwrap();
+            assert!(pattern.validate(&params).is_ok());
+            let points = generate_2d_trajectory_points_pattern(pattern, &params, 8., 0.5);
+            assert_eq!(points.len(), 17);
+            assert_eq!(points[0], (0., Metres2D::zero()));
+            if pattern == Pattern::StopAndGo {
+                // moves one amplitude every period
+                assert!((points[8].1.x - 2.).abs() < 1e-9 && (points[16].1.x - 4.).abs() < 1e-9);
+            } else {
+                // back at the start after every period
+                assert!(points[8].1.length() < 1e-9 && points[16].1.length() < 1e-9);
+            }
+        }
+        assert!(Pattern::from_name("spiral").is_none());
+
+        let circle = generate_2d_trajectory_points_pattern(Pattern::Circle, &params, 4., 1.);
+        // a quarter of the way round, starting at the top and going anticlockwise
+        assert!((circle[1].1 - Metres2D::new(-1., -1.)).length() < 1e-9);
+
+        for i in 0..=40 {
+            let pos = rounded_square_position(2., 0.5, i as f64 / 40.);
+            assert!(pos.x.abs().max(pos.y.abs()) <= 2. + 1e-9);
+        }
+        assert!((rounded_square_position(2., 0.5, 0.25) - Metres2D::new(2., -1.5)).length() < 1e-9);
+        let too_round = PatternParams {
+            radius: 3.,
+            ..params
+        };
+        assert!(Pattern::RoundedSquare.validate(&too_round).is_err());
+
+        let oscillation =
+            generate_1d_trajectory_points_pattern(Pattern::Sinusoid, &params, 2., 1.).unwrap();
+        assert_eq!(oscillation.len(), 3);
+        assert!((oscillation[1].1 + 2.).abs() < 1e-9 && (oscillation[2].1 + 4.).abs() < 1e-9);
+        assert!(generate_1d_trajectory_points_pattern(Pattern::Circle, &params, 2., 1.).is_err());
+    }
+
+    #[test]
+    fn kinematic_limits() {
+        // reverses instantly, then turns a right angle
+        let trajectory = NaiveTrajectory::from_points(
+            0.1,
+            vec![
+                (0., Metres2D::zero()),
+                (1., Metres2D::new(0.5, 0.)),
+                (2., Metres2D::zero()),
+                (3., Metres2D::new(0., 0.5)),
+            ],
+        );
+        let limits = KinematicLimits {
+            max_accel: Some(1.),
+            max_jerk: Some(20.),
+            max_turn_rate: Some(2.),
+        };
+        let report = FeasibilityReport::new(&trajectory);
+        assert!((report.peak_speed - 0.5).abs() < 1e-9);
+        assert!((report.peak_accel - 10.).abs() < 1e-6);
+        assert!((report.peak_turn_rate - PI / 0.1).abs() < 1e-6);
+        assert!(report.is_feasible(0.5, &Default::default()));
+        assert!(!report.is_feasible(0.4, &Default::default()));
+        assert!(!report.is_feasible(0.5, &limits));
+
+        let filtered = limit_kinematics(&trajectory, 0.5, &limits);
+        assert_eq!(filtered.data().len(), trajectory.data().len());
+        let report = FeasibilityReport::new(&filtered);
+        assert!(report.peak_accel <= 1. + 1e-9);
+        assert!(report.peak_turn_rate <= 2. + 1e-9);
+        assert!(report.peak_speed <= 0.5 + 1e-9);
+        // still follows the reference, ending up moving in the final direction
+        let last = filtered.data().len() - 1;
+        let end = filtered.data()[last] - filtered.data()[last - 1];
+        assert!(end.y > 0.);
+
+        // in 1D only the speed, acceleration and jerk are limited
+        let line = NaiveTrajectory::from_points(0.1, vec![(0., 0.), (1., 1.), (2., 0.)]);
+        let filtered = limit_kinematics(&line, 1., &limits);
+        let report = FeasibilityReport::new(&filtered);
+        assert!(report.is_feasible(1., &limits));
+        assert!(filtered.data().iter().all(|&x| x >= 0.));
+    }
+
+    #[test]
+    fn trajectory_3d() {
+        let points = generate_3d_trajectory_points_simple(
+            0.5,
+            10.,
+            2.,
+            0.1,
+            1.,
+            0.2,
+            &Default::default(),
+        );
+        assert_eq!(points[0], (0., Metres3D::zero()));
+        assert!(points[points.len() - 1].0 >= 10.);
+        for pair in points.windows(2) {
+            let (start, from) = pair[0];
+            let (end, to) = pair[1];
+            assert!((to - from).length() <= 0.5 * (end - start) + 1e-9);
+            assert!((to.z - from.z).abs() <= 0.2 * (end - start) + 1e-9);
+        }
+        let trajectory = NaiveTrajectory::from_points(0.1, points);
+        assert!(trajectory.data().len() >= 101);
+    }
+}